@@ -0,0 +1,89 @@
+use std::convert::TryInto;
+
+use tokio::io::{AsyncRead, AsyncReadExt, Take};
+
+use crate::*;
+
+/// Reads serialized values from a `tokio::io::AsyncRead`.
+///
+/// This is the async counterpart of `ByteReader` and `StringReader`,
+/// reading the input in chunks into a `PushParser`.
+/// Use `Value::from_async_source` to read values.
+pub struct AsyncReader<R, S = Vec<u8>> {
+    read: Take<R>,
+    parser: PushParser<S>,
+    chunk: Box<[u8]>,
+}
+
+/// Reads an `AsyncRead` into a `Value<Vec<u8>>`.
+pub type AsyncByteReader<R> = AsyncReader<R, Vec<u8>>;
+
+/// Reads an `AsyncRead` into a `Value<String>`.
+pub type AsyncStringReader<R> = AsyncReader<R, String>;
+
+/// The number of bytes requested from the `AsyncRead` at a time.
+const CHUNK_SIZE: usize = 8192;
+
+impl<R: AsyncRead + Unpin, S: OwnedStr> AsyncReader<R, S> {
+    /// Creates a new `AsyncReader`.
+    ///
+    /// The `read` does not need to be buffered;
+    /// the implementation reads it in chunks.
+    ///
+    /// The `limit` value is the maximum number of bytes read from `read`,
+    /// which avoids buffering arbitrarily large input.
+    /// Like `ByteReader`, lengths in the input that exceed `limit` are rejected.
+    pub fn new(read: R, limit: usize) -> Self {
+        Self::with_options(read, limit, &ParseOptions::default())
+    }
+
+    /// Creates a new `AsyncReader` with the specified parsing limits.
+    ///
+    /// The limits apply to each value separately.
+    pub fn with_options(read: R, limit: usize, options: &ParseOptions) -> Self {
+        Self {
+            read: read.take(
+                limit
+                    .try_into()
+                    .expect("Limit greater than u64::MAX_VALUE is not supported"),
+            ),
+            parser: PushParser::with_limit(limit, options),
+            chunk: vec![0; CHUNK_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the number of bytes consumed by the values read so far.
+    pub fn offset(&self) -> usize {
+        self.parser.offset()
+    }
+
+    async fn read_value(&mut self) -> IoResult<Value<S>> {
+        if self.parser.is_finished() {
+            self.parser.restart();
+        }
+        loop {
+            match self.parser.read_value()? {
+                Progress::Ready(value) => return Ok(value),
+                Progress::NeedMoreData => {
+                    let len = self.read.read(&mut self.chunk).await?;
+                    if len == 0 {
+                        return Err(Error::UnexpectedEof.into());
+                    }
+                    self.parser.feed(self.chunk.get(..len).unwrap_or_default());
+                }
+                Progress::Finished => return Err(Error::UnexpectedEof.into()),
+            }
+        }
+    }
+}
+
+impl<S: OwnedStr> Value<S> {
+    /// Parses the next value from an async stream.
+    ///
+    /// Successive calls with the same `source` read successive values.
+    pub async fn from_async_source<R: AsyncRead + Unpin>(
+        source: &mut AsyncReader<R, S>,
+    ) -> IoResult<Self> {
+        source.read_value().await
+    }
+}
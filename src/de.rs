@@ -0,0 +1,1084 @@
+use std::marker::PhantomData;
+use std::str;
+use std::vec;
+
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, DeserializeSeed, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize};
+
+use crate::parse::{
+    expect_char, read_array_header, read_array_key, read_bool, read_enum, read_float, read_int,
+    read_null, read_object_header, read_property_name, read_string, read_value_tagged,
+    skip_value_tagged, Limits,
+};
+use crate::*;
+
+/// Deserializes an instance of `T` from a serialized string.
+///
+/// Strings in `T` may borrow from `str`.
+pub fn from_str<'de, T: Deserialize<'de>>(str: &'de str) -> Result<T, SerdeError> {
+    from_str_with(str, &ParseOptions::default())
+}
+
+/// Deserializes an instance of `T` from a serialized string with the specified limits.
+pub fn from_str_with<'de, T: Deserialize<'de>>(
+    str: &'de str,
+    options: &ParseOptions,
+) -> Result<T, SerdeError> {
+    from_source_with(Cursor::new(str), options)
+}
+
+/// Deserializes an instance of `T` from serialized bytes.
+///
+/// Strings and byte slices in `T` may borrow from `bytes`.
+pub fn from_bytes<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, SerdeError> {
+    from_bytes_with(bytes, &ParseOptions::default())
+}
+
+/// Deserializes an instance of `T` from serialized bytes with the specified limits.
+pub fn from_bytes_with<'de, T: Deserialize<'de>>(
+    bytes: &'de [u8],
+    options: &ParseOptions,
+) -> Result<T, SerdeError> {
+    from_source_with(Cursor::new(bytes), options)
+}
+
+/// Deserializes an instance of `T` from a `Source`.
+///
+/// Strings in `T` may borrow from the source
+/// if the `Str` type of the source is a reference type.
+pub fn from_source<'de, S: Str<'de>, Src: Source<'de, S>, T: Deserialize<'de>>(
+    source: Src,
+) -> Result<T, SerdeError> {
+    from_source_with(source, &ParseOptions::default())
+}
+
+/// Deserializes an instance of `T` from a `Source` with the specified limits.
+pub fn from_source_with<'de, S: Str<'de>, Src: Source<'de, S>, T: Deserialize<'de>>(
+    source: Src,
+    options: &ParseOptions,
+) -> Result<T, SerdeError> {
+    let mut de = Deserializer::with_options(source, options);
+    T::deserialize(&mut de)
+}
+
+/// A serde `Deserializer` that reads directly from a `Source`.
+///
+/// Enum cases are visited as their case names.
+/// Arrays with sequential int keys starting from 0 are visited as sequences,
+/// and other arrays are visited as maps with int or string keys.
+/// Since this can only be known after the last key,
+/// the values of such arrays are buffered unless the type requests a sequence or a map.
+/// If the type requests a sequence, the keys must be sequential ints starting from 0.
+/// Objects are visited as maps from property names to values,
+/// discarding the visibility of the properties.
+/// Objects of classes disallowed by the options are visited as incomplete objects,
+/// with the original class in a leading `__PHP_Incomplete_Class_Name` entry.
+/// `Serializable` objects and references are not supported.
+pub struct Deserializer<'de, S: Str<'de>, Src: Source<'de, S>> {
+    source: Src,
+    /// A tag byte that was read by `deserialize_option` but not consumed
+    peeked: Option<u8>,
+    limits: Limits,
+    _ph: PhantomData<&'de S>,
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> Deserializer<'de, S, Src> {
+    /// Creates a deserializer that reads from `source`.
+    pub fn new(source: Src) -> Self {
+        Self::with_options(source, &ParseOptions::default())
+    }
+
+    /// Creates a deserializer that reads from `source` with the specified limits.
+    pub fn with_options(source: Src, options: &ParseOptions) -> Self {
+        Self {
+            source,
+            peeked: None,
+            limits: Limits::new(options.clone()),
+            _ph: PhantomData,
+        }
+    }
+
+    /// Consumes the deserializer and returns the underlying source.
+    pub fn into_source(self) -> Src {
+        self.source
+    }
+
+    fn next_tag(&mut self) -> IoResult<u8> {
+        match self.peeked.take() {
+            Some(tag) => Ok(tag),
+            None => self.source.read_u8_char(),
+        }
+    }
+
+    fn peek_tag(&mut self) -> IoResult<u8> {
+        let tag = self.next_tag()?;
+        self.peeked = Some(tag);
+        Ok(tag)
+    }
+
+    /// Visits an array as a sequence if it is a list, or as a map otherwise.
+    ///
+    /// Values are buffered until a key is not the next sequential index.
+    /// The buffering is only needed because `deserialize_any` has no type hint;
+    /// `deserialize_seq` and `deserialize_map` know the expected shape
+    /// and stream the entries with `visit_list` and `visit_map_array` instead,
+    /// so they should not call this.
+    fn visit_array<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        let len = read_array_header(&mut self.source, &mut self.limits)?;
+        let mut list = Vec::new();
+        let mut index = 0;
+        while list.len() < len {
+            let key = read_array_key(&mut self.source, &mut self.limits)?;
+            if !matches!(key, ArrayKey::Int(int) if int == index) {
+                let remaining = len - list.len() - 1;
+                return self.visit_map_entries(len, list, Some(key), remaining, visitor);
+            }
+            let tag = self.source.read_u8_char()?;
+            list.push(read_value_tagged(&mut self.source, tag, &mut self.limits)?);
+            index += 1;
+        }
+        let mut access = BufferedSeq {
+            values: list.into_iter(),
+        };
+        let value = visitor.visit_seq(&mut access)?;
+        self.end_compound(len, access.values.len())?;
+        Ok(value)
+    }
+
+    fn visit_map_array<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        let len = read_array_header(&mut self.source, &mut self.limits)?;
+        self.visit_map_entries(len, Vec::new(), None, len, visitor)
+    }
+
+    /// Visits the entries of an array as a map,
+    /// starting with the buffered values of the leading sequential keys
+    /// and the key that was read after them.
+    fn visit_map_entries<V: Visitor<'de>>(
+        &mut self,
+        len: usize,
+        buffered: Vec<Value<S>>,
+        key: Option<ArrayKey<S>>,
+        remaining: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let mut access = ArrayAccess {
+            de: &mut *self,
+            buffered: buffered.into_iter(),
+            index: 0,
+            value: None,
+            key,
+            remaining,
+        };
+        let value = visitor.visit_map(&mut access)?;
+        let unread = access.len();
+        self.end_compound(len, unread)?;
+        Ok(value)
+    }
+
+    fn visit_list<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        let len = read_array_header(&mut self.source, &mut self.limits)?;
+        let mut access = ListAccess {
+            de: &mut *self,
+            remaining: len,
+            index: 0,
+        };
+        let value = visitor.visit_seq(&mut access)?;
+        let remaining = access.remaining;
+        self.end_compound(len, remaining)?;
+        Ok(value)
+    }
+
+    fn visit_object<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value, SerdeError> {
+        // the `O` tag has been read
+        let start = self.source.offset().saturating_sub(1);
+        let (class, len) = read_object_header(&mut self.source, &mut self.limits)?;
+        let allowed = self.limits.check_class(class.as_bytes(), start)?;
+        let mut access = ObjectAccess {
+            de: &mut *self,
+            class_key: if allowed { None } else { Some(class) },
+            class_value: None,
+            remaining: len,
+        };
+        let value = visitor.visit_map(&mut access)?;
+        let remaining = access.remaining;
+        self.end_compound(len, remaining)?;
+        Ok(value)
+    }
+
+    fn end_compound(&mut self, len: usize, remaining: usize) -> Result<(), SerdeError> {
+        if remaining > 0 {
+            return Err(de::Error::invalid_length(
+                len,
+                &"fewer elements in the array or object",
+            ));
+        }
+        expect_char(&mut self.source, b'}')?;
+        self.limits.leave();
+        Ok(())
+    }
+
+    fn unsupported(&self, what: &str) -> SerdeError {
+        SerdeError::Custom(format!(
+            "{} cannot be deserialized (at offset {})",
+            what,
+            self.source.offset()
+        ))
+    }
+}
+
+/// Visits `string` as a string if it is valid UTF-8, or as bytes otherwise.
+///
+/// If `bytes` is true, it is always visited as bytes.
+fn visit_str<'de, S: Str<'de>, V: Visitor<'de>>(
+    string: S,
+    bytes: bool,
+    visitor: V,
+) -> Result<V::Value, SerdeError> {
+    match string.borrowed_bytes() {
+        Some(borrowed) => match str::from_utf8(borrowed) {
+            Ok(str) if !bytes => visitor.visit_borrowed_str(str),
+            _ => visitor.visit_borrowed_bytes(borrowed),
+        },
+        None => match str::from_utf8(string.as_bytes()) {
+            Ok(str) if !bytes => visitor.visit_str(str),
+            _ => visitor.visit_bytes(string.as_bytes()),
+        },
+    }
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> de::Deserializer<'de>
+    for &mut Deserializer<'de, S, Src>
+{
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.next_tag()? {
+            b'N' => {
+                read_null(&mut self.source)?;
+                visitor.visit_unit()
+            }
+            b'b' => visitor.visit_bool(read_bool(&mut self.source)?),
+            b'i' => visitor.visit_i64(read_int(&mut self.source)?),
+            b'd' => visitor.visit_f64(read_float(&mut self.source)?),
+            b's' => visit_str(
+                read_string(&mut self.source, &mut self.limits)?,
+                false,
+                visitor,
+            ),
+            b'a' => self.visit_array(visitor),
+            b'O' => self.visit_object(visitor),
+            b'C' => Err(self.unsupported("Serializable objects")),
+            b'E' => visit_str(
+                read_enum(&mut self.source, &mut self.limits)?.into_case(),
+                false,
+                visitor,
+            ),
+            b'r' | b'R' => Err(self.unsupported("references")),
+            _ => Err(Error::BadToken(self.source.offset()).into()),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.peek_tag()? {
+            b's' => {
+                self.peeked = None;
+                visit_str(
+                    read_string(&mut self.source, &mut self.limits)?,
+                    false,
+                    visitor,
+                )
+            }
+            b'i' => {
+                self.peeked = None;
+                visitor.visit_string(read_int(&mut self.source)?.to_string())
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.peek_tag()? {
+            b's' => {
+                self.peeked = None;
+                visit_str(
+                    read_string(&mut self.source, &mut self.limits)?,
+                    true,
+                    visitor,
+                )
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        if self.peek_tag()? == b'N' {
+            self.peeked = None;
+            read_null(&mut self.source)?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.peek_tag()? {
+            b'a' => {
+                self.peeked = None;
+                self.visit_list(visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.peek_tag()? {
+            b'a' => {
+                self.peeked = None;
+                self.visit_map_array(visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.next_tag()? {
+            b's' => {
+                let variant = read_string(&mut self.source, &mut self.limits)?;
+                visitor.visit_enum(UnitVariantAccess {
+                    variant: ArrayKey::String(variant),
+                })
+            }
+            b'E' => {
+                let case = read_enum(&mut self.source, &mut self.limits)?;
+                visitor.visit_enum(UnitVariantAccess {
+                    variant: ArrayKey::String(case.into_case()),
+                })
+            }
+            b'a' => {
+                let len = read_array_header(&mut self.source, &mut self.limits)?;
+                if len != 1 {
+                    return Err(de::Error::invalid_length(len, &"an array with one entry"));
+                }
+                let value = visitor.visit_enum(VariantAccess { de: &mut *self })?;
+                expect_char(&mut self.source, b'}')?;
+                self.limits.leave();
+                Ok(value)
+            }
+            _ => Err(Error::BadToken(self.source.offset()).into()),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        let tag = self.next_tag()?;
+        skip_value_tagged(&mut self.source, tag, &mut self.limits)?;
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+        unit unit_struct
+    }
+}
+
+/// Visits the entries of an array as a map.
+struct ArrayAccess<'a, 'de, S: Str<'de>, Src: Source<'de, S>> {
+    de: &'a mut Deserializer<'de, S, Src>,
+    /// Values of the leading sequential keys that were read before the array was found not to be a list
+    buffered: vec::IntoIter<Value<S>>,
+    /// The key of the next buffered value
+    index: i64,
+    /// The buffered value of the last visited key
+    value: Option<Value<S>>,
+    /// The key that was read after the buffered values
+    key: Option<ArrayKey<S>>,
+    /// The number of entries not read from the source yet
+    remaining: usize,
+}
+
+impl<'a, 'de, S: Str<'de>, Src: Source<'de, S>> ArrayAccess<'a, 'de, S, Src> {
+    fn len(&self) -> usize {
+        self.buffered.len() + usize::from(self.key.is_some()) + self.remaining
+    }
+}
+
+impl<'a, 'de, S: Str<'de>, Src: Source<'de, S>> de::MapAccess<'de>
+    for ArrayAccess<'a, 'de, S, Src>
+{
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        if let Some(value) = self.buffered.next() {
+            self.value = Some(value);
+            let key = ArrayKey::<S>::Int(self.index);
+            self.index += 1;
+            return seed.deserialize(KeyDeserializer { key }).map(Some);
+        }
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => {
+                if self.remaining == 0 {
+                    return Ok(None);
+                }
+                self.remaining -= 1;
+                read_array_key(&mut self.de.source, &mut self.de.limits)?
+            }
+        };
+        seed.deserialize(KeyDeserializer { key }).map(Some)
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(BufferedDeserializer { value }),
+            None => seed.deserialize(&mut *self.de),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// Visits the values of an array with sequential keys as a sequence.
+struct ListAccess<'a, 'de, S: Str<'de>, Src: Source<'de, S>> {
+    de: &'a mut Deserializer<'de, S, Src>,
+    remaining: usize,
+    index: i64,
+}
+
+impl<'a, 'de, S: Str<'de>, Src: Source<'de, S>> de::SeqAccess<'de> for ListAccess<'a, 'de, S, Src> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        match read_array_key(&mut self.de.source, &mut self.de.limits)? {
+            ArrayKey::Int(index) if index == self.index => {}
+            _ => {
+                return Err(SerdeError::Custom(format!(
+                    "array is not a list, expected key {} (at offset {})",
+                    self.index,
+                    self.de.source.offset()
+                )))
+            }
+        }
+        self.index += 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Visits the properties of an object as a map.
+struct ObjectAccess<'a, 'de, S: Str<'de>, Src: Source<'de, S>> {
+    de: &'a mut Deserializer<'de, S, Src>,
+    /// The original class of an incomplete object,
+    /// visited as the `__PHP_Incomplete_Class_Name` property before the others
+    class_key: Option<S>,
+    /// The original class after the `__PHP_Incomplete_Class_Name` key was visited
+    class_value: Option<S>,
+    remaining: usize,
+}
+
+impl<'a, 'de, S: Str<'de>, Src: Source<'de, S>> de::MapAccess<'de>
+    for ObjectAccess<'a, 'de, S, Src>
+{
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        if let Some(class) = self.class_key.take() {
+            self.class_value = Some(class);
+            return seed
+                .deserialize(BorrowedStrDeserializer::new(INCOMPLETE_CLASS_NAME))
+                .map(Some);
+        }
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let name = read_property_name(&mut self.de.source, &mut self.de.limits)?;
+        seed.deserialize(KeyDeserializer {
+            key: ArrayKey::String(name.into_name()),
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        match self.class_value.take() {
+            Some(class) => seed.deserialize(KeyDeserializer {
+                key: ArrayKey::String(class),
+            }),
+            None => seed.deserialize(&mut *self.de),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining + usize::from(self.class_key.is_some()))
+    }
+}
+
+/// Deserializes an array key or property name that has already been read.
+struct KeyDeserializer<S> {
+    key: ArrayKey<S>,
+}
+
+impl<'de, S: Str<'de>> de::Deserializer<'de> for KeyDeserializer<S> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.key {
+            ArrayKey::Int(int) => visitor.visit_i64(int),
+            ArrayKey::String(string) => visit_str(string, false, visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.key {
+            ArrayKey::Int(int) => visitor.visit_string(int.to_string()),
+            ArrayKey::String(string) => visit_str(string, false, visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.key {
+            ArrayKey::Int(int) => visitor.visit_byte_buf(int.to_string().into_bytes()),
+            ArrayKey::String(string) => visit_str(string, true, visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char
+        option unit unit_struct seq tuple tuple_struct map struct enum ignored_any
+    }
+}
+
+/// Accesses an enum variant represented as a string.
+struct UnitVariantAccess<S> {
+    variant: ArrayKey<S>,
+}
+
+impl<'de, S: Str<'de>> de::EnumAccess<'de> for UnitVariantAccess<S> {
+    type Error = SerdeError;
+    type Variant = UnitOnly;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, UnitOnly), SerdeError> {
+        let value = seed.deserialize(KeyDeserializer { key: self.variant })?;
+        Ok((value, UnitOnly))
+    }
+}
+
+/// The content of an enum variant that can only be a unit variant.
+struct UnitOnly;
+
+impl<'de> de::VariantAccess<'de> for UnitOnly {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"newtype variant",
+        ))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
+
+/// Accesses an enum variant represented as a single-entry array
+/// from the variant name to the variant content.
+struct VariantAccess<'a, 'de, S: Str<'de>, Src: Source<'de, S>> {
+    de: &'a mut Deserializer<'de, S, Src>,
+}
+
+impl<'a, 'de, S: Str<'de>, Src: Source<'de, S>> de::EnumAccess<'de>
+    for VariantAccess<'a, 'de, S, Src>
+{
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self), SerdeError> {
+        let key = read_array_key(&mut self.de.source, &mut self.de.limits)?;
+        let value = seed.deserialize(KeyDeserializer { key })?;
+        Ok((value, self))
+    }
+}
+
+impl<'a, 'de, S: Str<'de>, Src: Source<'de, S>> de::VariantAccess<'de>
+    for VariantAccess<'a, 'de, S, Src>
+{
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+/// Deserializes a value that was buffered while reading an array.
+struct BufferedDeserializer<S> {
+    value: Value<S>,
+}
+
+/// Returns whether the keys of `entries` are sequential ints starting from 0.
+fn is_list<S>(entries: &[(ArrayKey<S>, Value<S>)]) -> bool {
+    entries
+        .iter()
+        .zip(0..)
+        .all(|((key, _), index)| matches!(key, ArrayKey::Int(int) if *int == index))
+}
+
+fn buffered_unsupported(what: &str) -> SerdeError {
+    SerdeError::Custom(format!("{} cannot be deserialized", what))
+}
+
+impl<'de, S: Str<'de>> BufferedDeserializer<S> {
+    fn visit_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Array(entries) => visitor.visit_map(BufferedMap {
+                class_key: None,
+                class_value: None,
+                entries: entries.into_iter(),
+                value: None,
+            }),
+            Value::Object(object) => {
+                let incomplete = object.incomplete();
+                let (class, properties) = object.into_parts();
+                let entries: Vec<_> = properties
+                    .into_iter()
+                    .map(|(name, value)| (ArrayKey::String(name.into_name()), value))
+                    .collect();
+                visitor.visit_map(BufferedMap {
+                    class_key: if incomplete { Some(class) } else { None },
+                    class_value: None,
+                    entries: entries.into_iter(),
+                    value: None,
+                })
+            }
+            value => de::Deserializer::deserialize_any(BufferedDeserializer { value }, visitor),
+        }
+    }
+}
+
+impl<'de, S: Str<'de>> de::Deserializer<'de> for BufferedDeserializer<S> {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(bool) => visitor.visit_bool(bool),
+            Value::Int(int) => visitor.visit_i64(int),
+            Value::Float(float) => visitor.visit_f64(float),
+            Value::String(string) => visit_str(string, false, visitor),
+            Value::Array(entries) if is_list(&entries) => {
+                let values: Vec<_> = entries.into_iter().map(|(_, value)| value).collect();
+                visitor.visit_seq(BufferedSeq {
+                    values: values.into_iter(),
+                })
+            }
+            value @ Value::Array(_) | value @ Value::Object(_) => {
+                BufferedDeserializer { value }.visit_map(visitor)
+            }
+            Value::Serializable(_) => Err(buffered_unsupported("Serializable objects")),
+            Value::Enum(case) => visit_str(case.into_case(), false, visitor),
+            Value::Reference(_) => Err(buffered_unsupported("references")),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Int(int) => visitor.visit_string(int.to_string()),
+            value => de::Deserializer::deserialize_any(BufferedDeserializer { value }, visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::String(string) => visit_str(string, true, visitor),
+            value => de::Deserializer::deserialize_any(BufferedDeserializer { value }, visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(BufferedDeserializer { value }),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::Array(entries) if !is_list(&entries) => {
+                Err(SerdeError::Custom("array is not a list".to_string()))
+            }
+            value => de::Deserializer::deserialize_any(BufferedDeserializer { value }, visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.visit_map(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.visit_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.value {
+            Value::String(variant) => visitor.visit_enum(UnitVariantAccess {
+                variant: ArrayKey::String(variant),
+            }),
+            Value::Enum(case) => visitor.visit_enum(UnitVariantAccess {
+                variant: ArrayKey::String(case.into_case()),
+            }),
+            Value::Array(entries) => {
+                let len = entries.len();
+                let mut entries = entries.into_iter();
+                match (entries.next(), entries.next()) {
+                    (Some((key, value)), None) => {
+                        visitor.visit_enum(BufferedVariant { key, value })
+                    }
+                    _ => Err(de::Error::invalid_length(len, &"an array with one entry")),
+                }
+            }
+            _ => Err(SerdeError::Custom(
+                "expected a string, an enum case or an array".to_string(),
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+        unit unit_struct
+    }
+}
+
+/// Visits buffered array values as a sequence.
+struct BufferedSeq<S> {
+    values: vec::IntoIter<Value<S>>,
+}
+
+impl<'de, S: Str<'de>> de::SeqAccess<'de> for BufferedSeq<S> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        match self.values.next() {
+            Some(value) => seed.deserialize(BufferedDeserializer { value }).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+/// Visits buffered array entries or object properties as a map.
+struct BufferedMap<S> {
+    /// The original class of an incomplete object,
+    /// visited as the `__PHP_Incomplete_Class_Name` property before the others
+    class_key: Option<S>,
+    /// The original class after the `__PHP_Incomplete_Class_Name` key was visited
+    class_value: Option<S>,
+    entries: vec::IntoIter<(ArrayKey<S>, Value<S>)>,
+    /// The value of the last visited key
+    value: Option<Value<S>>,
+}
+
+impl<'de, S: Str<'de>> de::MapAccess<'de> for BufferedMap<S> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        if let Some(class) = self.class_key.take() {
+            self.class_value = Some(class);
+            return seed
+                .deserialize(BorrowedStrDeserializer::new(INCOMPLETE_CLASS_NAME))
+                .map(Some);
+        }
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(KeyDeserializer { key }).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        if let Some(class) = self.class_value.take() {
+            return seed.deserialize(KeyDeserializer {
+                key: ArrayKey::String(class),
+            });
+        }
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| SerdeError::Custom("value is missing".to_string()))?;
+        seed.deserialize(BufferedDeserializer { value })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len() + usize::from(self.class_key.is_some()))
+    }
+}
+
+/// Accesses a buffered enum variant represented as a single-entry array.
+struct BufferedVariant<S> {
+    key: ArrayKey<S>,
+    value: Value<S>,
+}
+
+impl<'de, S: Str<'de>> de::EnumAccess<'de> for BufferedVariant<S> {
+    type Error = SerdeError;
+    type Variant = BufferedDeserializer<S>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, BufferedDeserializer<S>), SerdeError> {
+        let variant = seed.deserialize(KeyDeserializer { key: self.key })?;
+        Ok((variant, BufferedDeserializer { value: self.value }))
+    }
+}
+
+impl<'de, S: Str<'de>> de::VariantAccess<'de> for BufferedDeserializer<S> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.visit_map(visitor)
+    }
+}
@@ -0,0 +1,364 @@
+use std::fmt::{self, Write};
+
+use getset::{CopyGetters, Getters};
+
+use crate::event::{EventState, ValueBuilder};
+use crate::parse::Limits;
+use crate::*;
+
+/// The number of input bytes shown on each side of the caret by `ParseError::render`.
+const SNIPPET_CONTEXT: usize = 32;
+
+/// A parsing error with the context needed to locate it in a large document.
+///
+/// This is returned by `Value::parse_detailed`.
+/// Tracking the context costs extra work for every array key and property name,
+/// so hot paths should use `Value::parse` and its `Error` instead.
+#[derive(Debug, Clone, CopyGetters, Getters)]
+pub struct ParseError {
+    /// The underlying parsing error.
+    #[getset(get_copy = "pub")]
+    error: Error,
+    /// The offset of the offending byte,
+    /// or the length of the input if the input ended unexpectedly.
+    #[getset(get_copy = "pub")]
+    offset: usize,
+    /// The logical position in the value where the error occurred.
+    #[getset(get = "pub")]
+    path: Path,
+    /// What the parser expected at `offset`, if applicable.
+    #[getset(get_copy = "pub")]
+    expected: Option<Expected>,
+    /// The offending byte, or `None` if the input ended unexpectedly.
+    #[getset(get_copy = "pub")]
+    found: Option<u8>,
+}
+
+impl ParseError {
+    /// Renders the error message followed by a snippet of `input` around the offending byte.
+    ///
+    /// `input` must be the input that produced this error.
+    /// Non-printable bytes are shown as `\xNN` escapes.
+    ///
+    /// ```text
+    /// encountered invalid token at offset 13: expected `}`, found `]`
+    ///   |
+    ///   | a:1:{i:0;i:1;]
+    ///   |              ^
+    /// ```
+    pub fn render(&self, input: &[u8]) -> String {
+        let start = self.offset.saturating_sub(SNIPPET_CONTEXT);
+        let end = input.len().min(self.offset.saturating_add(SNIPPET_CONTEXT));
+
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push_str("...");
+        }
+        escape_into(
+            &mut snippet,
+            input.get(start..self.offset).unwrap_or_default(),
+        );
+        let caret = snippet.len();
+        escape_into(
+            &mut snippet,
+            input.get(self.offset..end).unwrap_or_default(),
+        );
+        if end < input.len() {
+            snippet.push_str("...");
+        }
+
+        format!(
+            "{}\n  |\n  | {}\n  | {:caret$}^\n",
+            self,
+            snippet,
+            "",
+            caret = caret
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.write_message(f)?;
+        write!(f, " at offset {}", self.offset)?;
+        if !self.path.segments.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        if let Some(expected) = self.expected {
+            write!(f, ": expected {}, found ", expected)?;
+            match self.found {
+                Some(byte) => write!(f, "`{}`", Escaped(&[byte]))?,
+                None => write!(f, "end of input")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// What the parser expected when it encountered an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(variant_size_differences)]
+pub enum Expected {
+    /// The type tag of a value, such as `i` or `a`.
+    Value,
+    /// An `i` or `s` array key.
+    ArrayKey,
+    /// An `s` property name.
+    PropertyName,
+    /// The specified character.
+    Char(u8),
+    /// The `0` or `1` of a bool.
+    Bool,
+    /// A number terminated by the specified character.
+    Number(u8),
+    /// The specified number of bytes of string data.
+    Bytes(usize),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Value => write!(f, "a value"),
+            Self::ArrayKey => write!(f, "an int or string array key"),
+            Self::PropertyName => write!(f, "a string property name"),
+            Self::Char(char) => write!(f, "`{}`", Escaped(&[*char])),
+            Self::Bool => write!(f, "`0` or `1`"),
+            Self::Number(delim) => write!(f, "a number followed by `{}`", Escaped(&[*delim])),
+            Self::Bytes(n) => write!(f, "string data of length {}", n),
+        }
+    }
+}
+
+/// The logical position of a value within the root value,
+/// displayed like `["cart"][3]->price`.
+#[derive(Debug, Clone, Default, Getters)]
+pub struct Path {
+    /// The keys from the root value to the value, outermost first.
+    #[getset(get = "pub")]
+    segments: Vec<PathSegment>,
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in &self.segments {
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// One step in a `Path`.
+#[derive(Debug, Clone)]
+pub enum PathSegment {
+    /// An array entry with an int key.
+    Index(i64),
+    /// An array entry with a string key.
+    Key(Vec<u8>),
+    /// An object property, without its visibility mangling.
+    Property(Vec<u8>),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "[{}]", index),
+            Self::Key(key) => write!(f, "[\"{}\"]", Escaped(key)),
+            Self::Property(name) => write!(f, "->{}", Escaped(name)),
+        }
+    }
+}
+
+/// Displays printable ASCII as-is and other bytes as `\xNN`.
+struct Escaped<'t>(&'t [u8]);
+
+impl<'t> fmt::Display for Escaped<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &byte in self.0 {
+            if byte == b' ' || byte.is_ascii_graphic() {
+                f.write_char(char::from(byte))?;
+            } else {
+                write!(f, "\\x{:02x}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn escape_into(buf: &mut String, bytes: &[u8]) {
+    write!(buf, "{}", Escaped(bytes)).expect("writing to a String cannot fail");
+}
+
+impl<'de, S: Str<'de>> Value<S> {
+    /// Parses a string or byte array like `parse`,
+    /// but reports the path and expectation of any error.
+    pub fn parse_detailed(source: S) -> Result<Self, ParseError> {
+        Self::parse_detailed_with(source, &ParseOptions::default())
+    }
+
+    /// Parses a string or byte array with the specified limits like `parse_with`,
+    /// but reports the path and expectation of any error.
+    pub fn parse_detailed_with(source: S, options: &ParseOptions) -> Result<Self, ParseError> {
+        let mut recorder = Recorder {
+            cursor: Cursor::new(source),
+            last: None,
+        };
+        let mut state = EventState::new();
+        let mut limits = Limits::new(options.clone());
+        let mut builder = ValueBuilder::new();
+        let mut path = Path::default();
+
+        loop {
+            let start = recorder.cursor.offset();
+            let context = state.expected();
+            let result = state
+                .next_event(&mut recorder, &mut limits)
+                .map_err(|err| match err {
+                    IoError::Phpser(err) => err,
+                    IoError::Io(_) => unreachable!("Cursor does not perform IO"),
+                })
+                .and_then(|event| {
+                    let event = event.ok_or(Error::UnexpectedEof)?;
+                    let ends_value = match event.kind() {
+                        EventKind::Key(key) => {
+                            path.segments.push(match key {
+                                ArrayKey::Int(index) => PathSegment::Index(*index),
+                                ArrayKey::String(key) => PathSegment::Key(key.as_bytes().to_vec()),
+                            });
+                            false
+                        }
+                        EventKind::PropertyName(name) => {
+                            path.segments
+                                .push(PathSegment::Property(name.name().as_bytes().to_vec()));
+                            false
+                        }
+                        EventKind::ArrayStart(_) | EventKind::ObjectStart { .. } => false,
+                        _ => true,
+                    };
+                    Ok((ends_value, builder.push(event, &mut limits)?))
+                });
+            match result {
+                Ok((_, Some(value))) => return Ok(value),
+                Ok((true, None)) => {
+                    // an entry of an array or object has ended
+                    let _ = path.segments.pop();
+                }
+                Ok((false, None)) => {}
+                Err(error) => {
+                    return Err(recorder.diagnose(error, start, context, path));
+                }
+            }
+        }
+    }
+}
+
+/// A `Cursor` that remembers the last read operation,
+/// so that errors can be attributed to what the parser was reading.
+struct Recorder<S> {
+    cursor: Cursor<S>,
+    last: Option<Read>,
+}
+
+#[derive(Clone, Copy)]
+struct Read {
+    start: usize,
+    kind: ReadKind,
+    failed: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[allow(variant_size_differences)]
+enum ReadKind {
+    Byte,
+    Char(u8),
+    Until(u8),
+    Str(usize),
+}
+
+impl Read {
+    /// Returns what this read expected,
+    /// where `start` is the offset of the event and `context` is what the event starts with.
+    fn expected(self, start: usize, context: Expected) -> Expected {
+        match self.kind {
+            ReadKind::Byte if self.start == start => context,
+            ReadKind::Byte => Expected::Bool,
+            ReadKind::Char(char) => Expected::Char(char),
+            ReadKind::Until(delim) => Expected::Number(delim),
+            ReadKind::Str(n) => Expected::Bytes(n),
+        }
+    }
+}
+
+impl<'de, S: Str<'de>> Recorder<S> {
+    fn record<T>(
+        &mut self,
+        kind: ReadKind,
+        read: impl FnOnce(&mut Cursor<S>) -> IoResult<T>,
+    ) -> IoResult<T> {
+        let start = self.cursor.offset();
+        let result = read(&mut self.cursor);
+        self.last = Some(Read {
+            start,
+            kind,
+            failed: result.is_err(),
+        });
+        result
+    }
+
+    /// Attributes `error` to the last read of the event starting at `start`.
+    fn diagnose(&self, error: Error, start: usize, context: Expected, path: Path) -> ParseError {
+        let input = self.cursor.as_bytes();
+        let read = self.last.filter(|read| read.start >= start);
+        let (offset, expected) = match (error, read) {
+            (Error::UnexpectedEof, read) => {
+                (input.len(), read.map(|read| read.expected(start, context)))
+            }
+            (Error::BadToken(_), Some(read))
+                if read.failed || (read.kind == ReadKind::Byte && read.start == start) =>
+            {
+                (read.start, Some(read.expected(start, context)))
+            }
+            (Error::BadEncoding(_), Some(read))
+            | (Error::BadNumber(_), Some(read))
+            | (Error::BadArrayKeyType(_), Some(read))
+            | (Error::BadObjectKeyType(_), Some(read)) => {
+                (read.start, Some(read.expected(start, context)))
+            }
+            _ => (start, None),
+        };
+        ParseError {
+            error,
+            offset,
+            path,
+            expected,
+            found: input.get(offset).copied(),
+        }
+    }
+}
+
+impl<'de, S: Str<'de>> Source<'de, S> for Recorder<S> {
+    fn offset(&self) -> usize {
+        self.cursor.offset()
+    }
+
+    fn limit(&self) -> usize {
+        self.cursor.limit()
+    }
+
+    fn read_u8_char(&mut self) -> IoResult<u8> {
+        self.record(ReadKind::Byte, |cursor| cursor.read_u8_char())
+    }
+
+    fn expect_u8_char(&mut self, char: u8) -> IoResult {
+        self.record(ReadKind::Char(char), |cursor| cursor.expect_u8_char(char))
+    }
+
+    fn read_str(&mut self, n: usize) -> IoResult<S> {
+        self.record(ReadKind::Str(n), |cursor| cursor.read_str(n))
+    }
+
+    unsafe fn read_until(&mut self, byte: u8) -> IoResult<S> {
+        self.record(ReadKind::Until(byte), |cursor| cursor.read_until(byte))
+    }
+}
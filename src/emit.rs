@@ -1 +1,427 @@
+use std::convert::TryFrom;
+use std::io::{self, Write};
 
+use crate::*;
+
+/// Options for emitting serialized values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmitOptions {
+    /// The format of `d:` values.
+    pub float_precision: FloatPrecision,
+}
+
+/// The number of significant digits used for floats,
+/// corresponding to the `serialize_precision` ini setting of PHP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatPrecision {
+    /// The shortest representation that round-trips to the same value.
+    ///
+    /// This is equivalent to `serialize_precision=-1`, the default since PHP 7.1.
+    #[default]
+    Shortest,
+    /// A fixed number of significant digits, with trailing zeros removed.
+    ///
+    /// PHP before 7.1 used `serialize_precision=17`.
+    /// A value of 0 is treated as 1, like PHP does.
+    Digits(u8),
+}
+
+impl<'de, S: Str<'de>> Value<S> {
+    /// Appends the serialized form of this value to `buf`.
+    pub fn emit(&self, buf: &mut Vec<u8>) {
+        self.emit_with(buf, &EmitOptions::default());
+    }
+
+    /// Appends the serialized form of this value to `buf` with the specified options.
+    pub fn emit_with(&self, buf: &mut Vec<u8>, options: &EmitOptions) {
+        self.write_to_with(buf, options)
+            .expect("Writing to a Vec<u8> never fails");
+    }
+
+    /// Serializes this value into a new byte vector.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.emit(&mut buf);
+        buf
+    }
+
+    /// Writes the serialized form of this value to `write`.
+    ///
+    /// The output is not buffered;
+    /// consider wrapping `write` with an `io::BufWriter`.
+    pub fn write_to(&self, write: impl Write) -> io::Result<()> {
+        self.write_to_with(write, &EmitOptions::default())
+    }
+
+    /// Writes the serialized form of this value to `write` with the specified options.
+    pub fn write_to_with(&self, mut write: impl Write, options: &EmitOptions) -> io::Result<()> {
+        write_value(&mut write, self, options)
+    }
+}
+
+fn write_value<'de, S: Str<'de>>(
+    write: &mut impl Write,
+    value: &Value<S>,
+    options: &EmitOptions,
+) -> io::Result<()> {
+    match value {
+        Value::Null => write.write_all(b"N;"),
+        Value::Bool(bool) => write_bool(write, *bool),
+        Value::Int(int) => write_int(write, *int),
+        Value::Float(float) => write_float(write, *float, options.float_precision),
+        Value::String(string) => write_string(write, string.as_bytes()),
+        Value::Array(array) => {
+            write!(write, "a:{}:{{", array.len())?;
+            for (key, item) in array {
+                match key {
+                    ArrayKey::Int(int) => write_int(write, *int)?,
+                    ArrayKey::String(string) => write_string(write, string.as_bytes())?,
+                }
+                write_value(write, item, options)?;
+            }
+            write.write_all(b"}")
+        }
+        Value::Object(object) => {
+            write_object_header(
+                write,
+                object.class().as_bytes(),
+                object.incomplete(),
+                object.properties().len(),
+            )?;
+            for (name, property) in object.properties() {
+                write_property_name(write, name)?;
+                write_value(write, property, options)?;
+            }
+            write.write_all(b"}")
+        }
+        Value::Serializable(ser) => write_ser(write, ser),
+        Value::Enum(case) => {
+            write_enum_case(write, case.class().as_bytes(), case.case().as_bytes())
+        }
+        Value::Reference(r#ref) => write_ref(write, *r#ref),
+    }
+}
+
+impl<'de, S: Str<'de>> Graph<S> {
+    /// Appends the serialized form of this graph to `buf`.
+    ///
+    /// See `Graph::write_to_with` for how shared nodes are emitted.
+    ///
+    /// # Panics
+    /// Panics if an edge points to a node that was not created by this graph.
+    pub fn emit(&self, buf: &mut Vec<u8>) {
+        self.emit_with(buf, &EmitOptions::default());
+    }
+
+    /// Appends the serialized form of this graph to `buf` with the specified options.
+    ///
+    /// # Panics
+    /// Panics if an edge points to a node that was not created by this graph.
+    pub fn emit_with(&self, buf: &mut Vec<u8>, options: &EmitOptions) {
+        self.write_to_with(buf, options)
+            .expect("Edges must point to nodes in the graph");
+    }
+
+    /// Serializes this graph into a new byte vector.
+    ///
+    /// # Panics
+    /// Panics if an edge points to a node that was not created by this graph.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.emit(&mut buf);
+        buf
+    }
+
+    /// Writes the serialized form of this graph to `write`.
+    ///
+    /// The output is not buffered;
+    /// consider wrapping `write` with an `io::BufWriter`.
+    pub fn write_to(&self, write: impl Write) -> io::Result<()> {
+        self.write_to_with(write, &EmitOptions::default())
+    }
+
+    /// Writes the serialized form of this graph to `write` with the specified options.
+    ///
+    /// Slot numbers are assigned in the same way as PHP's `serialize`.
+    /// When a node is reached again after it has been emitted:
+    ///
+    /// - through an edge with `Edge::is_ref`, it is emitted as `R:`;
+    /// - through any other edge to an object, `Serializable` object or enum case,
+    ///   it is emitted as `r:`, so the handles stay identical after unserialization;
+    /// - through any other edge to an array or a scalar, it is emitted again as a copy,
+    ///   unless the edge is part of a cycle, in which case it is emitted as `R:`.
+    ///
+    /// # Errors
+    /// Returns an `io::ErrorKind::InvalidInput` error
+    /// if an edge points to a node that was not created by this graph.
+    pub fn write_to_with(&self, mut write: impl Write, options: &EmitOptions) -> io::Result<()> {
+        let mut emitter = GraphEmitter {
+            graph: self,
+            options,
+            slots: vec![None; self.len()],
+            open: vec![false; self.len()],
+            next_slot: 1,
+        };
+        emitter.write_edge(&mut write, Edge::new(self.root(), false))
+    }
+}
+
+/// Tracks the slots assigned to graph nodes during emission.
+struct GraphEmitter<'t, S> {
+    graph: &'t Graph<S>,
+    options: &'t EmitOptions,
+    /// The slot of the first occurrence of each node
+    slots: Vec<Option<usize>>,
+    /// Whether each node is a container that is currently being written
+    open: Vec<bool>,
+    next_slot: usize,
+}
+
+impl<'t, 'de, S: Str<'de>> GraphEmitter<'t, S> {
+    fn write_edge(&mut self, write: &mut impl Write, edge: Edge) -> io::Result<()> {
+        let id = edge.target();
+        let node = self.graph.get(id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "edge points to a node outside the graph",
+            )
+        })?;
+        let index = id.index();
+
+        if let Some(slot) = self.slots.get(index).copied().flatten() {
+            if edge.is_ref() {
+                return write_ref(write, Ref::new(slot));
+            }
+            if let Node::Object { .. } | Node::Serializable(_) | Node::Enum(_) = node {
+                self.next_slot += 1;
+                return write_ref(write, Ref::new_object(slot));
+            }
+            if self.open.get(index).copied().unwrap_or(false) {
+                return write_ref(write, Ref::new(slot));
+            }
+        }
+
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        if let Some(entry @ None) = self.slots.get_mut(index) {
+            *entry = Some(slot);
+        }
+
+        match node {
+            Node::Null => write.write_all(b"N;"),
+            Node::Bool(bool) => write_bool(write, *bool),
+            Node::Int(int) => write_int(write, *int),
+            Node::Float(float) => write_float(write, *float, self.options.float_precision),
+            Node::String(string) => write_string(write, string.as_bytes()),
+            Node::Array(array) => {
+                self.set_open(index, true);
+                write!(write, "a:{}:{{", array.len())?;
+                for (key, item) in array {
+                    match key {
+                        ArrayKey::Int(int) => write_int(write, *int)?,
+                        ArrayKey::String(string) => write_string(write, string.as_bytes())?,
+                    }
+                    self.write_edge(write, *item)?;
+                }
+                self.set_open(index, false);
+                write.write_all(b"}")
+            }
+            Node::Object {
+                class,
+                properties,
+                incomplete,
+            } => {
+                self.set_open(index, true);
+                write_object_header(write, class.as_bytes(), *incomplete, properties.len())?;
+                for (name, property) in properties {
+                    write_property_name(write, name)?;
+                    self.write_edge(write, *property)?;
+                }
+                self.set_open(index, false);
+                write.write_all(b"}")
+            }
+            Node::Serializable(ser) => write_ser(write, ser),
+            Node::Enum(case) => {
+                write_enum_case(write, case.class().as_bytes(), case.case().as_bytes())
+            }
+        }
+    }
+
+    fn set_open(&mut self, index: usize, open: bool) {
+        if let Some(entry) = self.open.get_mut(index) {
+            *entry = open;
+        }
+    }
+}
+
+fn write_ser<'de, S: Str<'de>>(write: &mut impl Write, ser: &Serializable<S>) -> io::Result<()> {
+    write_class(write, b'C', ser.class().as_bytes())?;
+    let data = ser.data().as_bytes();
+    write!(write, ":{}:{{", data.len())?;
+    write.write_all(data)?;
+    write.write_all(b"}")
+}
+
+pub(crate) fn write_bool(write: &mut impl Write, bool: bool) -> io::Result<()> {
+    write.write_all(if bool { b"b:1;" } else { b"b:0;" })
+}
+
+pub(crate) fn write_int(write: &mut impl Write, int: i64) -> io::Result<()> {
+    write!(write, "i:{};", int)
+}
+
+pub(crate) fn write_float(
+    write: &mut impl Write,
+    float: f64,
+    precision: FloatPrecision,
+) -> io::Result<()> {
+    write!(write, "d:{};", format_float(float, precision))
+}
+
+/// Formats a float in the same way as `php_gcvt` does with `serialize_precision`.
+pub(crate) fn format_float(float: f64, precision: FloatPrecision) -> String {
+    if float.is_nan() {
+        return "NAN".into();
+    }
+    if float.is_infinite() {
+        return if float > 0. { "INF" } else { "-INF" }.into();
+    }
+
+    // `ndigit` is the maximum decimal exponent before switching to the exponential format.
+    let (ndigit, scientific) = match precision {
+        FloatPrecision::Shortest => (17, format!("{:e}", float.abs())),
+        FloatPrecision::Digits(digits) => {
+            let digits = digits.max(1);
+            (
+                i32::from(digits),
+                format!("{:.*e}", usize::from(digits) - 1, float.abs()),
+            )
+        }
+    };
+    let (mantissa, exp) = scientific.split_at(
+        str::find(&scientific, 'e').expect("Scientific format always contains an exponent"),
+    );
+    let exp = exp
+        .trim_start_matches('e')
+        .parse::<i32>()
+        .expect("Scientific format always contains an integer exponent");
+
+    let mut digits = mantissa.replace('.', "");
+    while digits.len() > 1 && digits.ends_with('0') {
+        let _ = digits.pop();
+    }
+    // the position of the decimal point relative to the start of `digits`
+    let decpt = exp + 1;
+
+    let mut out = String::new();
+    if float.is_sign_negative() {
+        out.push('-');
+    }
+
+    if decpt < -3 || decpt > ndigit {
+        let (first, rest) = digits.split_at(1);
+        out.push_str(first);
+        out.push('.');
+        out.push_str(if rest.is_empty() { "0" } else { rest });
+        out.push('E');
+        out.push(if decpt < 1 { '-' } else { '+' });
+        out.push_str(&(decpt - 1).abs().to_string());
+    } else if decpt < 0 {
+        out.push_str("0.");
+        for _ in decpt..0 {
+            out.push('0');
+        }
+        out.push_str(&digits);
+    } else {
+        let decpt = usize::try_from(decpt).expect("decpt is non-negative");
+        if digits.len() <= decpt {
+            out.push_str(&digits);
+            for _ in digits.len()..decpt {
+                out.push('0');
+            }
+        } else {
+            let (int, frac) = digits.split_at(decpt);
+            out.push_str(if int.is_empty() { "0" } else { int });
+            out.push('.');
+            out.push_str(frac);
+        }
+    }
+    out
+}
+
+pub(crate) fn write_ref(write: &mut impl Write, r#ref: Ref) -> io::Result<()> {
+    let tag = match r#ref.kind() {
+        RefKind::Object => 'r',
+        RefKind::Value => 'R',
+    };
+    write!(write, "{}:{};", tag, r#ref.index())
+}
+
+pub(crate) fn write_string(write: &mut impl Write, string: &[u8]) -> io::Result<()> {
+    write!(write, "s:{}:\"", string.len())?;
+    write.write_all(string)?;
+    write.write_all(b"\";")
+}
+
+/// Writes the `X:len:"class"` prefix shared by `O:` and `C:` values.
+pub(crate) fn write_class(write: &mut impl Write, tag: u8, class: &[u8]) -> io::Result<()> {
+    write.write_all(&[tag])?;
+    write!(write, ":{}:\"", class.len())?;
+    write.write_all(class)?;
+    write.write_all(b"\"")
+}
+
+/// Writes the part of an object before its properties,
+/// including the `__PHP_Incomplete_Class_Name` property of incomplete objects.
+///
+/// Incomplete objects downgraded from `C:` values are written as `O:` too,
+/// since their payload was discarded when parsing.
+fn write_object_header(
+    write: &mut impl Write,
+    class: &[u8],
+    incomplete: bool,
+    len: usize,
+) -> io::Result<()> {
+    if incomplete {
+        write_class(write, b'O', INCOMPLETE_CLASS.as_bytes())?;
+        write!(write, ":{}:{{", len + 1)?;
+        write_string(write, INCOMPLETE_CLASS_NAME.as_bytes())?;
+        write_string(write, class)
+    } else {
+        write_class(write, b'O', class)?;
+        write!(write, ":{}:{{", len)
+    }
+}
+
+/// Writes a PHP 8.1 enum case as `E:len:"Class:Case";`.
+pub(crate) fn write_enum_case(write: &mut impl Write, class: &[u8], case: &[u8]) -> io::Result<()> {
+    write!(write, "E:{}:\"", class.len() + case.len() + 1)?;
+    write.write_all(class)?;
+    write.write_all(b":")?;
+    write.write_all(case)?;
+    write.write_all(b"\";")
+}
+
+/// Writes a property name with the visibility mangling undone by `read_object`.
+fn write_property_name<'de, S: Str<'de>>(
+    write: &mut impl Write,
+    name: &PropertyName<S>,
+) -> io::Result<()> {
+    let bytes = name.name().as_bytes();
+    match name.vis() {
+        PropertyVis::Public => write_string(write, bytes),
+        PropertyVis::Protected => {
+            write!(write, "s:{}:\"\0*\0", bytes.len() + 3)?;
+            write.write_all(bytes)?;
+            write.write_all(b"\";")
+        }
+        PropertyVis::Private(class) => {
+            let class = class.as_bytes();
+            write!(write, "s:{}:\"\0", class.len() + bytes.len() + 2)?;
+            write.write_all(class)?;
+            write.write_all(b"\0")?;
+            write.write_all(bytes)?;
+            write.write_all(b"\";")
+        }
+    }
+}
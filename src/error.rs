@@ -2,7 +2,14 @@ use std::fmt;
 use std::io::ErrorKind;
 use std::result::Result as StdResult;
 
+use crate::ParseError;
+
 /// Either a parsing error or an IO error.
+///
+/// `IoError` does not depend on the `Str` type being parsed,
+/// so errors from parsing `&str`, `&[u8]`, `String` and `Vec<u8>` sources
+/// can be propagated through the same functions.
+#[derive(Debug)]
 pub enum IoError {
     /// A phpser parsing error
     Phpser(Error),
@@ -25,6 +32,42 @@ impl From<std::io::Error> for IoError {
     }
 }
 
+impl From<ParseError> for IoError {
+    fn from(err: ParseError) -> Self {
+        Self::Phpser(err.error())
+    }
+}
+
+/// Converts parsing errors to `io::ErrorKind::UnexpectedEof` or `io::ErrorKind::InvalidData`,
+/// so that `IoResult` can be propagated in functions returning `io::Result`.
+impl From<IoError> for std::io::Error {
+    fn from(err: IoError) -> Self {
+        match err {
+            IoError::Phpser(err @ Error::UnexpectedEof) => Self::new(ErrorKind::UnexpectedEof, err),
+            IoError::Phpser(err) => Self::new(ErrorKind::InvalidData, err),
+            IoError::Io(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Phpser(err) => write!(f, "{}", err),
+            Self::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for IoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Phpser(_) => None,
+        }
+    }
+}
+
 /// A parsing error.
 #[derive(Debug, Clone, Copy)]
 pub enum Error {
@@ -40,6 +83,24 @@ pub enum Error {
     BadArrayKeyType(usize),
     /// object key must be string
     BadObjectKeyType(usize),
+    /// enum value must be in the form `Class:Case`
+    BadEnumName(usize),
+    /// reference to a nonexistent value
+    ///
+    /// Unlike other variants, this contains the reference index instead of an offset.
+    BadReference(usize),
+    /// arrays and objects are nested deeper than `ParseOptions::max_depth`
+    DepthLimitExceeded(usize),
+    /// the document has more elements than `ParseOptions::max_elements`
+    ElementLimitExceeded(usize),
+    /// a string is longer than `ParseOptions::max_string_len`
+    StringLimitExceeded(usize),
+    /// the document requires more memory than `ParseOptions::max_allocation`
+    AllocationLimitExceeded(usize),
+    /// the class of the object starting at this offset is not allowed by `ParseOptions::allowed_classes`
+    DisallowedClass(usize),
+    /// unexpected data after the end of the value
+    TrailingData(usize),
 }
 
 impl Error {
@@ -52,12 +113,43 @@ impl Error {
             Self::BadNumber(offset) => Some(offset),
             Self::BadArrayKeyType(offset) => Some(offset),
             Self::BadObjectKeyType(offset) => Some(offset),
+            Self::BadEnumName(offset) => Some(offset),
+            Self::BadReference(_) => None,
+            Self::DepthLimitExceeded(offset) => Some(offset),
+            Self::ElementLimitExceeded(offset) => Some(offset),
+            Self::StringLimitExceeded(offset) => Some(offset),
+            Self::AllocationLimitExceeded(offset) => Some(offset),
+            Self::DisallowedClass(offset) => Some(offset),
+            Self::TrailingData(offset) => Some(offset),
         }
     }
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Error {
+    /// Adds `base` to the offset of this error, if relevant.
+    pub(crate) fn offset_by(self, base: usize) -> Self {
+        match self {
+            Self::UnexpectedEof => self,
+            Self::BadEncoding(offset) => Self::BadEncoding(offset + base),
+            Self::BadToken(offset) => Self::BadToken(offset + base),
+            Self::BadNumber(offset) => Self::BadNumber(offset + base),
+            Self::BadArrayKeyType(offset) => Self::BadArrayKeyType(offset + base),
+            Self::BadObjectKeyType(offset) => Self::BadObjectKeyType(offset + base),
+            Self::BadEnumName(offset) => Self::BadEnumName(offset + base),
+            Self::BadReference(_) => self,
+            Self::DepthLimitExceeded(offset) => Self::DepthLimitExceeded(offset + base),
+            Self::ElementLimitExceeded(offset) => Self::ElementLimitExceeded(offset + base),
+            Self::StringLimitExceeded(offset) => Self::StringLimitExceeded(offset + base),
+            Self::AllocationLimitExceeded(offset) => Self::AllocationLimitExceeded(offset + base),
+            Self::DisallowedClass(offset) => Self::DisallowedClass(offset + base),
+            Self::TrailingData(offset) => Self::TrailingData(offset + base),
+        }
+    }
+}
+
+impl Error {
+    /// Writes the description of this error without the offset.
+    pub(crate) fn write_message(self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::UnexpectedEof => write!(f, "unexpected end of document"),
             Self::BadEncoding(_) => write!(
@@ -68,7 +160,21 @@ impl fmt::Display for Error {
             Self::BadNumber(_) => write!(f, "encountered malformed or out-of-range number"),
             Self::BadArrayKeyType(_) => write!(f, "array key must be int or string"),
             Self::BadObjectKeyType(_) => write!(f, "object key must be string"),
-        }?;
+            Self::BadEnumName(_) => write!(f, "enum value must be in the form Class:Case"),
+            Self::BadReference(index) => write!(f, "reference to nonexistent value {}", index),
+            Self::DepthLimitExceeded(_) => write!(f, "maximum nesting depth exceeded"),
+            Self::ElementLimitExceeded(_) => write!(f, "maximum number of elements exceeded"),
+            Self::StringLimitExceeded(_) => write!(f, "maximum string length exceeded"),
+            Self::AllocationLimitExceeded(_) => write!(f, "maximum allocation size exceeded"),
+            Self::DisallowedClass(_) => write!(f, "class is not allowed"),
+            Self::TrailingData(_) => write!(f, "unexpected data after the end of the value"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_message(f)?;
         if let Some(offset) = self.offset() {
             write!(f, " at offset {}", offset)?;
         }
@@ -76,6 +182,78 @@ impl fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        err.error()
+    }
+}
+
+/// An error from the serde integration.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum SerdeError {
+    /// A phpser parsing error
+    Phpser(Error),
+    /// An IO error, excluding unexpected EOF error
+    Io(std::io::Error),
+    /// An error reported by serde or the (de)serialized type
+    Custom(String),
+}
+
+#[cfg(feature = "serde")]
+impl From<IoError> for SerdeError {
+    fn from(err: IoError) -> Self {
+        match err {
+            IoError::Phpser(err) => Self::Phpser(err),
+            IoError::Io(err) => Self::Io(err),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Error> for SerdeError {
+    fn from(err: Error) -> Self {
+        Self::Phpser(err)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Phpser(err) => write!(f, "{}", err),
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Custom(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for SerdeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::Custom(message.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self::Custom(message.to_string())
+    }
+}
+
 /// A parsing result.
 pub type Result<T = (), E = Error> = StdResult<T, E>;
 
@@ -0,0 +1,556 @@
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use derive_new::new;
+use getset::{CopyGetters, Getters};
+
+use crate::parse::{
+    expect_char, read_array_header, read_array_key, read_bool, read_enum, read_float, read_int,
+    read_null, read_object_header, read_property_name, read_ref, read_ser, read_string, Limits,
+};
+use crate::*;
+
+/// A syntactic event in a serialized value, as yielded by `EventParser`.
+#[derive(Debug, Clone, CopyGetters, Getters, new)]
+pub struct Event<S> {
+    /// The offset of the first byte of the event in the source.
+    #[getset(get_copy = "pub")]
+    offset: usize,
+    /// The kind of the event.
+    #[getset(get = "pub")]
+    kind: EventKind<S>,
+}
+
+impl<S> Event<S> {
+    /// Consumes the event and returns its kind.
+    pub fn into_kind(self) -> EventKind<S> {
+        self.kind
+    }
+}
+
+/// The kind of an `Event`.
+///
+/// An array is reported as `ArrayStart`,
+/// followed by a `Key` and the events of the value for each entry,
+/// followed by `End`.
+/// An object is reported in the same way,
+/// with `ObjectStart` and `PropertyName` instead of `ArrayStart` and `Key`.
+/// All other values are reported as a single event.
+#[derive(Debug, Clone)]
+pub enum EventKind<S> {
+    /// A null, bool, int, float or string value.
+    Scalar(Scalar<S>),
+    /// The start of an array with the specified number of entries.
+    ArrayStart(usize),
+    /// The key of the next array entry.
+    Key(ArrayKey<S>),
+    /// The start of a non-`Serializable` object.
+    ObjectStart {
+        /// The object class.
+        class: S,
+        /// The number of properties.
+        len: usize,
+        /// Whether the object was downgraded to an incomplete object
+        /// because its class is disallowed by `ParseOptions::allowed_classes`.
+        ///
+        /// Objects serialized as `__PHP_Incomplete_Class` are reported as-is.
+        /// A downgraded `Serializable` object is reported as an incomplete object
+        /// with no properties.
+        incomplete: bool,
+    },
+    /// The name of the next object property.
+    PropertyName(PropertyName<S>),
+    /// The end of the innermost array or object.
+    End,
+    /// A `Serializable` object.
+    Serializable(Serializable<S>),
+    /// A PHP 8.1 enum case.
+    Enum(EnumCase<S>),
+    /// A reference to another value.
+    Reference(Ref),
+}
+
+/// A value that is not an array, an object or a reference.
+#[derive(Debug, Clone)]
+pub enum Scalar<S> {
+    /// Corresponds to the `null` type of PHP.
+    Null,
+    /// Corresponds to the `bool` type of PHP.
+    Bool(bool),
+    /// Corresponds to the `int` type of PHP.
+    Int(i64),
+    /// Corresponds to the `float` type of PHP.
+    Float(f64),
+    /// Corresponds to the `string` type of PHP.
+    String(S),
+}
+
+impl<S> From<Scalar<S>> for Value<S> {
+    fn from(scalar: Scalar<S>) -> Self {
+        match scalar {
+            Scalar::Null => Value::Null,
+            Scalar::Bool(bool) => Value::Bool(bool),
+            Scalar::Int(int) => Value::Int(int),
+            Scalar::Float(float) => Value::Float(float),
+            Scalar::String(string) => Value::String(string),
+        }
+    }
+}
+
+/// A streaming parser that reads one serialized value from a `Source` as `Event`s,
+/// without materializing the value.
+///
+/// Parsing uses an explicit stack instead of recursion,
+/// so the nesting depth is only bounded by `ParseOptions::max_depth`.
+pub struct EventParser<'de, S: Str<'de>, Src: Source<'de, S>> {
+    source: Src,
+    state: EventState,
+    limits: Limits,
+    failed: bool,
+    _ph: PhantomData<&'de S>,
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> EventParser<'de, S, Src> {
+    /// Creates a parser that reads from `source`.
+    pub fn new(source: Src) -> Self {
+        Self::with_options(source, &ParseOptions::default())
+    }
+
+    /// Creates a parser that reads from `source` with the specified limits.
+    pub fn with_options(source: Src, options: &ParseOptions) -> Self {
+        Self {
+            source,
+            state: EventState::new(),
+            limits: Limits::new(options.clone()),
+            failed: false,
+            _ph: PhantomData,
+        }
+    }
+
+    /// Consumes the parser and returns the underlying source.
+    ///
+    /// If the value has been completely parsed,
+    /// the source is positioned right after it.
+    pub fn into_source(self) -> Src {
+        self.source
+    }
+
+    /// Returns the number of arrays and objects that have started but not ended.
+    pub fn depth(&self) -> usize {
+        self.state.stack.len()
+    }
+
+    /// Reads the next event,
+    /// or returns `None` if the value has been completely parsed.
+    ///
+    /// # Errors
+    /// Returns any error encountered in the source.
+    /// The parser must not be used after an error.
+    pub fn next_event(&mut self) -> IoResult<Option<Event<S>>> {
+        let result = self.state.next_event(&mut self.source, &mut self.limits);
+        if result.is_err() {
+            self.failed = true;
+        }
+        result
+    }
+
+    /// Reads the next value into a `Value`.
+    ///
+    /// This can be used to materialize selected parts of the input,
+    /// such as the value after a particular `Key` event.
+    ///
+    /// # Panics
+    /// Panics if the next event would not start a value,
+    /// i.e. unless no events have been read yet
+    /// or the last event was `Key` or `PropertyName`.
+    pub fn read_value(&mut self) -> IoResult<Value<S>> {
+        assert!(
+            self.state.expects_value(),
+            "EventParser::read_value called where no value is expected"
+        );
+        let result = build_value(&mut self.state, &mut self.source, &mut self.limits);
+        if result.is_err() {
+            self.failed = true;
+        }
+        result
+    }
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> Iterator for EventParser<'de, S, Src> {
+    type Item = IoResult<Event<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        self.next_event().transpose()
+    }
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> FusedIterator for EventParser<'de, S, Src> {}
+
+/// The position of an `EventParser` in the value, independent of the source.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventState {
+    /// The arrays and objects that have started but not ended
+    stack: Vec<Frame>,
+    /// A tag byte that was consumed before the parser was created
+    tag: Option<u8>,
+    /// Whether the root value has started
+    started: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    kind: FrameKind,
+    /// The number of entries whose value has not started
+    remaining: usize,
+    /// Whether the key of the next entry has been read
+    key_read: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Array,
+    Object,
+    /// A `Serializable` object downgraded to an incomplete object,
+    /// which has no `}` to read
+    Downgraded,
+}
+
+impl EventState {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a state for a value whose tag byte `tag` has already been consumed.
+    pub(crate) fn with_tag(tag: u8) -> Self {
+        Self {
+            tag: Some(tag),
+            ..Self::default()
+        }
+    }
+
+    /// Returns whether the root value has been completely parsed.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.started && self.stack.is_empty()
+    }
+
+    /// Returns whether the next event starts a value.
+    pub(crate) fn expects_value(&self) -> bool {
+        match self.stack.last() {
+            Some(frame) => frame.key_read,
+            None => !self.started,
+        }
+    }
+
+    /// Returns what the next event must start with.
+    pub(crate) fn expected(&self) -> Expected {
+        match self.stack.last() {
+            Some(frame) if frame.key_read => Expected::Value,
+            Some(frame) if frame.remaining == 0 => Expected::Char(b'}'),
+            Some(frame) if frame.kind == FrameKind::Array => Expected::ArrayKey,
+            Some(_) => Expected::PropertyName,
+            None => Expected::Value,
+        }
+    }
+
+    /// Reads the next event from `source`.
+    ///
+    /// The state is only changed if an event is returned successfully,
+    /// but `limits` may be changed in any case.
+    pub(crate) fn next_event<'de, S: Str<'de>, Src: Source<'de, S>>(
+        &mut self,
+        source: &mut Src,
+        limits: &mut Limits,
+    ) -> IoResult<Option<Event<S>>> {
+        let offset = source.offset();
+        match self.stack.last_mut() {
+            None if self.started => return Ok(None),
+            None => {}
+            Some(frame) if frame.key_read => {}
+            Some(frame) if frame.remaining == 0 => {
+                if frame.kind != FrameKind::Downgraded {
+                    expect_char(source, b'}')?;
+                    limits.leave();
+                }
+                let _ = self.stack.pop();
+                return Ok(Some(Event::new(offset, EventKind::End)));
+            }
+            Some(frame) => {
+                let kind = match frame.kind {
+                    FrameKind::Array => EventKind::Key(read_array_key(source, limits)?),
+                    _ => EventKind::PropertyName(read_property_name(source, limits)?),
+                };
+                frame.key_read = true;
+                return Ok(Some(Event::new(offset, kind)));
+            }
+        }
+
+        let (offset, tag) = match self.tag {
+            Some(tag) => (offset.saturating_sub(1), tag),
+            None => (offset, source.read_u8_char()?),
+        };
+        let (kind, frame) = match tag {
+            b'N' => {
+                read_null(source)?;
+                (EventKind::Scalar(Scalar::Null), None)
+            }
+            b'b' => (EventKind::Scalar(Scalar::Bool(read_bool(source)?)), None),
+            b'i' => (EventKind::Scalar(Scalar::Int(read_int(source)?)), None),
+            b'd' => (EventKind::Scalar(Scalar::Float(read_float(source)?)), None),
+            b's' => (
+                EventKind::Scalar(Scalar::String(read_string(source, limits)?)),
+                None,
+            ),
+            b'a' => {
+                let len = read_array_header(source, limits)?;
+                (
+                    EventKind::ArrayStart(len),
+                    Some(Frame::new(FrameKind::Array, len)),
+                )
+            }
+            b'O' => {
+                let (class, len) = read_object_header(source, limits)?;
+                let allowed = limits.check_class(class.as_bytes(), offset)?;
+                (
+                    EventKind::ObjectStart {
+                        class,
+                        len,
+                        incomplete: !allowed,
+                    },
+                    Some(Frame::new(FrameKind::Object, len)),
+                )
+            }
+            b'C' => {
+                let ser = read_ser(source, limits)?;
+                if limits.check_class(ser.class().as_bytes(), offset)? {
+                    (EventKind::Serializable(ser), None)
+                } else {
+                    let (class, _) = ser.into_parts();
+                    (
+                        EventKind::ObjectStart {
+                            class,
+                            len: 0,
+                            incomplete: true,
+                        },
+                        Some(Frame::new(FrameKind::Downgraded, 0)),
+                    )
+                }
+            }
+            b'E' => (EventKind::Enum(read_enum(source, limits)?), None),
+            b'r' => (
+                EventKind::Reference(read_ref(source, RefKind::Object)?),
+                None,
+            ),
+            b'R' => (
+                EventKind::Reference(read_ref(source, RefKind::Value)?),
+                None,
+            ),
+            _ => return Err(Error::BadToken(source.offset()).into()),
+        };
+
+        self.tag = None;
+        self.started = true;
+        if let Some(parent) = self.stack.last_mut() {
+            parent.remaining -= 1;
+            parent.key_read = false;
+        }
+        if let Some(frame) = frame {
+            self.stack.push(frame);
+        }
+        Ok(Some(Event::new(offset, kind)))
+    }
+}
+
+impl Frame {
+    fn new(kind: FrameKind, remaining: usize) -> Self {
+        Self {
+            kind,
+            remaining,
+            key_read: false,
+        }
+    }
+}
+
+/// An array or object under construction by `build_value`.
+enum Partial<S> {
+    Array {
+        entries: Vec<(ArrayKey<S>, Value<S>)>,
+        key: Option<ArrayKey<S>>,
+    },
+    Object {
+        class: S,
+        properties: Vec<(PropertyName<S>, Value<S>)>,
+        name: Option<PropertyName<S>>,
+        incomplete: bool,
+    },
+}
+
+/// Reads events from `state` until the next value is complete.
+///
+/// The next event from `state` must start a value.
+pub(crate) fn build_value<'de, S: Str<'de>, Src: Source<'de, S>>(
+    state: &mut EventState,
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<Value<S>> {
+    let mut builder = ValueBuilder::new();
+    loop {
+        let event = state
+            .next_event(source, limits)?
+            .ok_or(Error::UnexpectedEof)?;
+        if let Some(value) = builder.push(event, limits)? {
+            return Ok(value);
+        }
+    }
+}
+
+/// The maximum number of entries preallocated for an array or object.
+///
+/// Declared lengths are not trusted beyond this
+/// until the entries have actually been read,
+/// since a source without a byte limit cannot reject a huge `len` up front.
+const MAX_PREALLOCATED_ENTRIES: usize = 1024;
+
+/// Assembles a `Value` from the events of the value.
+pub(crate) struct ValueBuilder<S> {
+    stack: Vec<Partial<S>>,
+}
+
+impl<'de, S: Str<'de>> ValueBuilder<S> {
+    pub(crate) fn new() -> Self {
+        Self { stack: vec![] }
+    }
+
+    /// Adds the next event, returning the value if it is complete.
+    ///
+    /// The first event must start a value.
+    pub(crate) fn push(
+        &mut self,
+        event: Event<S>,
+        limits: &mut Limits,
+    ) -> Result<Option<Value<S>>> {
+        let stack = &mut self.stack;
+        let offset = event.offset();
+        let value = match event.into_kind() {
+            EventKind::Scalar(scalar) => scalar.into(),
+            EventKind::Serializable(ser) => Value::Serializable(ser),
+            EventKind::Enum(case) => Value::Enum(case),
+            EventKind::Reference(r#ref) => Value::Reference(r#ref),
+            EventKind::ArrayStart(len) => {
+                limits.allocate(
+                    len.saturating_mul(size_of::<(ArrayKey<S>, Value<S>)>()),
+                    offset,
+                )?;
+                stack.push(Partial::Array {
+                    entries: Vec::with_capacity(len.min(MAX_PREALLOCATED_ENTRIES)),
+                    key: None,
+                });
+                return Ok(None);
+            }
+            EventKind::ObjectStart {
+                class,
+                len,
+                incomplete,
+            } => {
+                limits.allocate(
+                    len.saturating_mul(size_of::<(PropertyName<S>, Value<S>)>()),
+                    offset,
+                )?;
+                stack.push(Partial::Object {
+                    class,
+                    properties: Vec::with_capacity(len.min(MAX_PREALLOCATED_ENTRIES)),
+                    name: None,
+                    incomplete,
+                });
+                return Ok(None);
+            }
+            EventKind::Key(new_key) => {
+                if let Some(Partial::Array { key, .. }) = stack.last_mut() {
+                    *key = Some(new_key);
+                }
+                return Ok(None);
+            }
+            EventKind::PropertyName(new_name) => {
+                if let Some(Partial::Object { name, .. }) = stack.last_mut() {
+                    *name = Some(new_name);
+                }
+                return Ok(None);
+            }
+            EventKind::End => stack
+                .pop()
+                .expect("EventState yields End only after a start event")
+                .into_value(),
+        };
+
+        match stack.last_mut() {
+            None => return Ok(Some(value)),
+            Some(Partial::Array { entries, key }) => {
+                let key = key
+                    .take()
+                    .expect("EventState yields a key before each value");
+                entries.push((key, value));
+            }
+            Some(Partial::Object {
+                properties, name, ..
+            }) => {
+                let name = name
+                    .take()
+                    .expect("EventState yields a property name before each value");
+                properties.push((name, value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'de, S: Str<'de>> Partial<S> {
+    fn into_value(self) -> Value<S> {
+        match self {
+            Partial::Array { entries, .. } => Value::Array(entries),
+            Partial::Object {
+                class,
+                properties,
+                incomplete: true,
+                ..
+            } => Value::Object(Object::new_incomplete(class, properties)),
+            Partial::Object {
+                class,
+                mut properties,
+                ..
+            } => {
+                if class
+                    .as_bytes()
+                    .eq_ignore_ascii_case(INCOMPLETE_CLASS.as_bytes())
+                {
+                    if let Some(original) = take_incomplete_class_name(&mut properties) {
+                        return Value::Object(Object::new_incomplete(original, properties));
+                    }
+                }
+                Value::Object(Object::new(class, properties))
+            }
+        }
+    }
+}
+
+/// Removes the leading `__PHP_Incomplete_Class_Name` property and returns its value.
+fn take_incomplete_class_name<'de, S: Str<'de>>(
+    properties: &mut Vec<(PropertyName<S>, Value<S>)>,
+) -> Option<S> {
+    let is_class_name = match properties.first() {
+        Some((name, Value::String(_))) => {
+            matches!(name.vis(), PropertyVis::Public)
+                && name.name().as_bytes() == INCOMPLETE_CLASS_NAME.as_bytes()
+        }
+        _ => false,
+    };
+    if !is_class_name {
+        return None;
+    }
+    match properties.remove(0).1 {
+        Value::String(original) => Some(original),
+        _ => None,
+    }
+}
@@ -0,0 +1,276 @@
+use derive_new::new;
+use getset::CopyGetters;
+
+use crate::*;
+
+/// A value graph in which references are resolved to shared nodes.
+///
+/// Nodes are stored in an arena and addressed by `NodeId`,
+/// so values shared by multiple containers and cyclic values are representable.
+#[derive(Debug, Clone)]
+pub struct Graph<S> {
+    nodes: Vec<Node<S>>,
+    root: NodeId,
+}
+
+/// The index of a node in a `Graph`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub(crate) fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A value in a `Graph`.
+///
+/// This is analogous to `Value`,
+/// except that children are edges to other nodes
+/// and references have been resolved.
+#[derive(Debug, Clone)]
+pub enum Node<S> {
+    /// Corresponds to the `null` type of PHP.
+    Null,
+    /// Corresponds to the `bool` type of PHP.
+    Bool(bool),
+    /// Corresponds to the `int` type of PHP.
+    Int(i64),
+    /// Corresponds to the `float` type of PHP.
+    Float(f64),
+    /// Corresponds to the `string` type of PHP.
+    String(S),
+    /// Corresponds to the `array` type of PHP.
+    Array(Vec<(ArrayKey<S>, Edge)>),
+    /// Corresponds to non-`Serializable` objects in PHP.
+    Object {
+        /// The object class.
+        class: S,
+        /// The object properties.
+        properties: Vec<(PropertyName<S>, Edge)>,
+        /// Whether this is an `__PHP_Incomplete_Class` object.
+        ///
+        /// See `Object::incomplete` for details.
+        incomplete: bool,
+    },
+    /// Corresponds to `Serializable` objects in PHP.
+    Serializable(Serializable<S>),
+    /// Corresponds to enum cases in PHP 8.1 and above.
+    Enum(EnumCase<S>),
+}
+
+/// A link from an array entry, an object property or the root to a node.
+#[derive(Debug, Clone, Copy, CopyGetters, new)]
+pub struct Edge {
+    /// The node that this edge points to.
+    #[getset(get_copy = "pub")]
+    target: NodeId,
+    /// Whether the location holds a PHP reference (`&`) to the node.
+    ///
+    /// All locations holding a reference to the same node
+    /// share the same variable in PHP.
+    #[getset(get_copy = "pub")]
+    is_ref: bool,
+}
+
+impl<S> Graph<S> {
+    /// Creates a graph containing only the root node.
+    pub fn new(root: Node<S>) -> Self {
+        Self {
+            nodes: vec![root],
+            root: NodeId(0),
+        }
+    }
+
+    /// Returns the root node of the graph.
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Changes the root node of the graph.
+    pub fn set_root(&mut self, root: NodeId) {
+        self.root = root;
+    }
+
+    /// Adds a node to the graph, returning its ID.
+    ///
+    /// The node is unreachable until an edge or the root points to it.
+    pub fn add(&mut self, node: Node<S>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(node);
+        id
+    }
+
+    /// Returns the node with the ID `id`,
+    /// or `None` if `id` was not created by this graph.
+    pub fn get(&self, id: NodeId) -> Option<&Node<S>> {
+        self.nodes.get(id.0)
+    }
+
+    /// Returns the node with the ID `id` mutably,
+    /// or `None` if `id` was not created by this graph.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<S>> {
+        self.nodes.get_mut(id.0)
+    }
+
+    /// Returns the number of nodes in the graph, including unreachable nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether the graph contains no nodes.
+    ///
+    /// This is always false since a graph always has a root.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<'de, S: Str<'de>> Graph<S> {
+    /// Resolves the references in a parsed value.
+    ///
+    /// Slots are numbered in the same way as PHP's `unserialize`:
+    /// the root is slot 1, every value including `r:` references takes a slot,
+    /// while array keys, property names and `R:` references do not.
+    /// The payload of a `Serializable` object is opaque and takes no slots.
+    ///
+    /// An `r:` reference resolves to the same node as its target,
+    /// while an `R:` reference additionally marks both locations with `Edge::is_ref`.
+    ///
+    /// # Errors
+    /// Returns `Error::BadReference` if a reference points to a slot that does not exist yet.
+    pub fn resolve(value: Value<S>) -> Result<Self> {
+        let mut resolver = Resolver {
+            nodes: vec![],
+            slots: vec![],
+        };
+        let root = resolver.resolve(value, None)?;
+
+        let Resolver { mut nodes, slots } = resolver;
+        for slot in slots {
+            if !slot.referenced {
+                continue;
+            }
+            if let Some((parent, index)) = slot.location {
+                let edge = match nodes.get_mut(parent.0) {
+                    Some(Node::Array(entries)) => entries.get_mut(index).map(|(_, edge)| edge),
+                    Some(Node::Object { properties, .. }) => {
+                        properties.get_mut(index).map(|(_, edge)| edge)
+                    }
+                    _ => None,
+                };
+                if let Some(edge) = edge {
+                    edge.is_ref = true;
+                }
+            }
+        }
+
+        Ok(Self {
+            nodes,
+            root: root.target,
+        })
+    }
+}
+
+impl<'de, S: Str<'de>> Value<S> {
+    /// Resolves the references in this value into a `Graph`.
+    ///
+    /// See `Graph::resolve` for details.
+    pub fn resolve(self) -> Result<Graph<S>> {
+        Graph::resolve(self)
+    }
+}
+
+struct Resolver<S> {
+    nodes: Vec<Node<S>>,
+    slots: Vec<Slot>,
+}
+
+/// A value that can be the target of a reference.
+struct Slot {
+    node: NodeId,
+    /// The container node and entry index holding the value, or `None` for the root
+    location: Option<(NodeId, usize)>,
+    /// Whether an `R:` reference points to this slot
+    referenced: bool,
+}
+
+impl<'de, S: Str<'de>> Resolver<S> {
+    fn resolve(&mut self, value: Value<S>, location: Option<(NodeId, usize)>) -> Result<Edge> {
+        let node = match value {
+            Value::Null => Node::Null,
+            Value::Bool(bool) => Node::Bool(bool),
+            Value::Int(int) => Node::Int(int),
+            Value::Float(float) => Node::Float(float),
+            Value::String(string) => Node::String(string),
+            Value::Serializable(ser) => Node::Serializable(ser),
+            Value::Enum(case) => Node::Enum(case),
+            Value::Array(entries) => {
+                let id = self.push(Node::Array(Vec::with_capacity(entries.len())), location);
+                for (index, (key, item)) in entries.into_iter().enumerate() {
+                    let edge = self.resolve(item, Some((id, index)))?;
+                    if let Some(Node::Array(edges)) = self.nodes.get_mut(id.0) {
+                        edges.push((key, edge));
+                    }
+                }
+                return Ok(Edge::new(id, false));
+            }
+            Value::Object(object) => {
+                let incomplete = object.incomplete();
+                let (class, properties) = object.into_parts();
+                let id = self.push(
+                    Node::Object {
+                        class,
+                        properties: Vec::with_capacity(properties.len()),
+                        incomplete,
+                    },
+                    location,
+                );
+                for (index, (name, item)) in properties.into_iter().enumerate() {
+                    let edge = self.resolve(item, Some((id, index)))?;
+                    if let Some(Node::Object {
+                        properties: edges, ..
+                    }) = self.nodes.get_mut(id.0)
+                    {
+                        edges.push((name, edge));
+                    }
+                }
+                return Ok(Edge::new(id, false));
+            }
+            Value::Reference(r#ref) => {
+                let slot = r#ref
+                    .index()
+                    .checked_sub(1)
+                    .and_then(|index| self.slots.get_mut(index))
+                    .ok_or_else(|| Error::BadReference(r#ref.index()))?;
+                let target = slot.node;
+                return Ok(match r#ref.kind() {
+                    RefKind::Object => {
+                        self.slots.push(Slot {
+                            node: target,
+                            location,
+                            referenced: false,
+                        });
+                        Edge::new(target, false)
+                    }
+                    RefKind::Value => {
+                        slot.referenced = true;
+                        Edge::new(target, true)
+                    }
+                });
+            }
+        };
+        Ok(Edge::new(self.push(node, location), false))
+    }
+
+    fn push(&mut self, node: Node<S>, location: Option<(NodeId, usize)>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(node);
+        self.slots.push(Slot {
+            node: id,
+            location,
+            referenced: false,
+        });
+        id
+    }
+}
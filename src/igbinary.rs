@@ -0,0 +1,649 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use crate::event::ValueBuilder;
+use crate::parse::{unmangle_property_name, Limits};
+use crate::*;
+
+/// The version header written by igbinary 2 and later.
+const VERSION: u32 = 2;
+/// The version header written by igbinary 1, which is still readable.
+const VERSION_1: u32 = 1;
+
+const TYPE_NULL: u8 = 0x00;
+const TYPE_REF8: u8 = 0x01;
+const TYPE_REF16: u8 = 0x02;
+const TYPE_REF32: u8 = 0x03;
+const TYPE_BOOL_FALSE: u8 = 0x04;
+const TYPE_BOOL_TRUE: u8 = 0x05;
+const TYPE_LONG8P: u8 = 0x06;
+const TYPE_LONG8N: u8 = 0x07;
+const TYPE_LONG16P: u8 = 0x08;
+const TYPE_LONG16N: u8 = 0x09;
+const TYPE_LONG32P: u8 = 0x0a;
+const TYPE_LONG32N: u8 = 0x0b;
+const TYPE_DOUBLE: u8 = 0x0c;
+const TYPE_STRING_EMPTY: u8 = 0x0d;
+const TYPE_STRING_ID8: u8 = 0x0e;
+const TYPE_STRING_ID16: u8 = 0x0f;
+const TYPE_STRING_ID32: u8 = 0x10;
+const TYPE_STRING8: u8 = 0x11;
+const TYPE_STRING16: u8 = 0x12;
+const TYPE_STRING32: u8 = 0x13;
+const TYPE_ARRAY8: u8 = 0x14;
+const TYPE_ARRAY16: u8 = 0x15;
+const TYPE_ARRAY32: u8 = 0x16;
+const TYPE_OBJECT8: u8 = 0x17;
+const TYPE_OBJECT16: u8 = 0x18;
+const TYPE_OBJECT32: u8 = 0x19;
+const TYPE_OBJECT_ID8: u8 = 0x1a;
+const TYPE_OBJECT_ID16: u8 = 0x1b;
+const TYPE_OBJECT_ID32: u8 = 0x1c;
+const TYPE_OBJECT_SER8: u8 = 0x1d;
+const TYPE_OBJECT_SER16: u8 = 0x1e;
+const TYPE_OBJECT_SER32: u8 = 0x1f;
+const TYPE_LONG64P: u8 = 0x20;
+const TYPE_LONG64N: u8 = 0x21;
+const TYPE_OBJREF8: u8 = 0x22;
+const TYPE_OBJREF16: u8 = 0x23;
+const TYPE_OBJREF32: u8 = 0x24;
+const TYPE_REF: u8 = 0x25;
+
+impl<'de, S: Str<'de>> Value<S> {
+    /// Parses the output of `igbinary_serialize`.
+    ///
+    /// igbinary only numbers arrays, objects and values marked as references,
+    /// so its reference ids are translated to the slots used by `Ref`,
+    /// and the result is the same `Value` that `parse` returns for the `serialize()` output.
+    ///
+    /// Enum cases are not supported.
+    ///
+    /// # Errors
+    /// Returns `Error::TrailingData` if there is input after the value,
+    /// like `igbinary_unserialize` does.
+    pub fn parse_igbinary(source: S) -> IoResult<Self> {
+        Self::parse_igbinary_with(source, &ParseOptions::default())
+    }
+
+    /// Parses the output of `igbinary_serialize` with the specified limits.
+    pub fn parse_igbinary_with(source: S, options: &ParseOptions) -> IoResult<Self> {
+        let mut decoder = Decoder {
+            source,
+            offset: 0,
+            strings: vec![],
+            ids: vec![],
+            slot: 0,
+            stack: vec![],
+            limits: Limits::new(options.clone()),
+        };
+
+        let version = decoder.read_u32()?;
+        if version != VERSION && version != VERSION_1 {
+            return Err(Error::BadToken(decoder.offset).into());
+        }
+
+        let mut builder = ValueBuilder::new();
+        let value = loop {
+            let event = decoder.next_event()?;
+            if let Some(value) = builder.push(event, &mut decoder.limits)? {
+                break value;
+            }
+        };
+        if decoder.offset < decoder.source.len() {
+            return Err(Error::TrailingData(decoder.offset).into());
+        }
+        Ok(value)
+    }
+
+    /// Serializes this value into a new byte vector in the format of `igbinary_serialize`.
+    ///
+    /// # Errors
+    /// See `write_igbinary`.
+    pub fn to_igbinary(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.write_igbinary(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Writes this value to `write` in the format of `igbinary_serialize`.
+    ///
+    /// Repeated strings, array keys, property names and class names
+    /// are written as back-references to their first occurrence,
+    /// like igbinary does with the default `igbinary.compact_strings=On`.
+    ///
+    /// The output is not buffered;
+    /// consider wrapping `write` with an `io::BufWriter`.
+    ///
+    /// # Errors
+    /// Returns an `io::ErrorKind::InvalidInput` error if the value contains an enum case,
+    /// or a reference to a slot that is not before the reference.
+    pub fn write_igbinary(&self, write: impl Write) -> io::Result<()> {
+        let mut targets = HashSet::new();
+        collect_targets(self, &mut targets);
+        let mut encoder = Encoder {
+            write,
+            strings: HashMap::new(),
+            targets,
+            slot: 0,
+            ids: vec![None],
+            next_id: 0,
+        };
+        encoder.write.write_all(&VERSION.to_be_bytes())?;
+        encoder.write_value(self)
+    }
+}
+
+/// Decodes igbinary data into `Event`s.
+struct Decoder<S> {
+    source: S,
+    offset: usize,
+    /// The offsets of the strings that can be referenced by a string id
+    strings: Vec<(usize, usize)>,
+    /// The slot of the value with each reference id
+    ids: Vec<usize>,
+    /// The slot of the last value
+    slot: usize,
+    stack: Vec<Frame>,
+    limits: Limits,
+}
+
+struct Frame {
+    kind: FrameKind,
+    /// The number of entries whose value has not started
+    remaining: usize,
+    /// Whether the key of the next entry has been read
+    key_read: bool,
+}
+
+#[derive(PartialEq, Eq)]
+enum FrameKind {
+    Array,
+    Object,
+    /// A `Serializable` object downgraded to an incomplete object,
+    /// which has no properties and was not counted by `Limits::enter`
+    Downgraded,
+}
+
+impl<'de, S: Str<'de>> Decoder<S> {
+    /// Reads the next event.
+    ///
+    /// Unlike PHP serialization, igbinary has no end marker,
+    /// so `End` is yielded as soon as the last entry has been read.
+    fn next_event(&mut self) -> IoResult<Event<S>> {
+        let offset = self.offset;
+        if let Some(frame) = self.stack.last_mut() {
+            if !frame.key_read {
+                if frame.remaining == 0 {
+                    if frame.kind != FrameKind::Downgraded {
+                        self.limits.leave();
+                    }
+                    let _ = self.stack.pop();
+                    return Ok(Event::new(offset, EventKind::End));
+                }
+                let object = frame.kind == FrameKind::Object;
+                frame.key_read = true;
+
+                let tag = self.read_u8()?;
+                let kind = match self.read_key(tag)? {
+                    ArrayKey::String(name) if object => {
+                        EventKind::PropertyName(unmangle_property_name(name, self.offset)?)
+                    }
+                    ArrayKey::Int(_) if object => {
+                        return Err(Error::BadObjectKeyType(self.offset).into())
+                    }
+                    key => EventKind::Key(key),
+                };
+                return Ok(Event::new(offset, kind));
+            }
+        }
+
+        let mut tag = self.read_u8()?;
+        let marked = tag == TYPE_REF;
+        if marked {
+            tag = self.read_u8()?;
+        }
+        let (kind, frame) = match tag {
+            TYPE_REF8 | TYPE_REF16 | TYPE_REF32 if !marked => {
+                let slot = self.read_ref(tag - TYPE_REF8)?;
+                (EventKind::Reference(Ref::new(slot)), None)
+            }
+            TYPE_OBJREF8 | TYPE_OBJREF16 | TYPE_OBJREF32 if !marked => {
+                let slot = self.read_ref(tag - TYPE_OBJREF8)?;
+                self.slot += 1;
+                (EventKind::Reference(Ref::new_object(slot)), None)
+            }
+            TYPE_ARRAY8 | TYPE_ARRAY16 | TYPE_ARRAY32 => {
+                let len = self.read_len(tag - TYPE_ARRAY8)?;
+                self.limits.enter(len, self.offset)?;
+                self.add_id();
+                (
+                    EventKind::ArrayStart(len),
+                    Some(Frame::new(FrameKind::Array, len)),
+                )
+            }
+            TYPE_OBJECT8 | TYPE_OBJECT16 | TYPE_OBJECT32 | TYPE_OBJECT_ID8 | TYPE_OBJECT_ID16
+            | TYPE_OBJECT_ID32 => {
+                let class = match tag {
+                    TYPE_OBJECT8 | TYPE_OBJECT16 | TYPE_OBJECT32 => {
+                        self.read_string(tag - TYPE_OBJECT8)?
+                    }
+                    _ => self.read_string_id(tag - TYPE_OBJECT_ID8)?,
+                };
+                self.add_id();
+                let allowed = self.limits.check_class(class.as_bytes(), offset)?;
+
+                let data_tag = self.read_u8()?;
+                match data_tag {
+                    TYPE_ARRAY8 | TYPE_ARRAY16 | TYPE_ARRAY32 => {
+                        let len = self.read_len(data_tag - TYPE_ARRAY8)?;
+                        self.limits.enter(len, self.offset)?;
+                        (
+                            EventKind::ObjectStart {
+                                class,
+                                len,
+                                incomplete: !allowed,
+                            },
+                            Some(Frame::new(FrameKind::Object, len)),
+                        )
+                    }
+                    TYPE_OBJECT_SER8 | TYPE_OBJECT_SER16 | TYPE_OBJECT_SER32 => {
+                        let len = self.read_size(data_tag - TYPE_OBJECT_SER8)?;
+                        self.limits.string(len, self.offset)?;
+                        let data = self.read_str(len)?;
+                        if allowed {
+                            (
+                                EventKind::Serializable(Serializable::new(class, data)),
+                                None,
+                            )
+                        } else {
+                            (
+                                EventKind::ObjectStart {
+                                    class,
+                                    len: 0,
+                                    incomplete: true,
+                                },
+                                Some(Frame::new(FrameKind::Downgraded, 0)),
+                            )
+                        }
+                    }
+                    _ => return Err(Error::BadToken(self.offset).into()),
+                }
+            }
+            TYPE_NULL | TYPE_BOOL_FALSE | TYPE_BOOL_TRUE | TYPE_DOUBLE | TYPE_LONG8P
+            | TYPE_LONG8N | TYPE_LONG16P | TYPE_LONG16N | TYPE_LONG32P | TYPE_LONG32N
+            | TYPE_LONG64P | TYPE_LONG64N | TYPE_STRING_EMPTY | TYPE_STRING_ID8
+            | TYPE_STRING_ID16 | TYPE_STRING_ID32 | TYPE_STRING8 | TYPE_STRING16
+            | TYPE_STRING32 => {
+                let scalar = match tag {
+                    TYPE_NULL => Scalar::Null,
+                    TYPE_BOOL_FALSE => Scalar::Bool(false),
+                    TYPE_BOOL_TRUE => Scalar::Bool(true),
+                    TYPE_DOUBLE => Scalar::Float(f64::from_bits(self.read_u64()?)),
+                    _ => match self.read_key(tag)? {
+                        ArrayKey::Int(int) => Scalar::Int(int),
+                        ArrayKey::String(string) => Scalar::String(string),
+                    },
+                };
+                if marked {
+                    self.add_id();
+                } else {
+                    self.slot += 1;
+                }
+                (EventKind::Scalar(scalar), None)
+            }
+            _ => return Err(Error::BadToken(self.offset).into()),
+        };
+
+        if let Some(parent) = self.stack.last_mut() {
+            parent.remaining -= 1;
+            parent.key_read = false;
+        }
+        if let Some(frame) = frame {
+            self.stack.push(frame);
+        }
+        Ok(Event::new(offset, kind))
+    }
+
+    /// Assigns the next slot to a value and gives it the next reference id.
+    fn add_id(&mut self) {
+        self.slot += 1;
+        self.ids.push(self.slot);
+    }
+
+    /// Reads an int or a string.
+    fn read_key(&mut self, tag: u8) -> IoResult<ArrayKey<S>> {
+        let key = match tag {
+            TYPE_LONG8P | TYPE_LONG16P | TYPE_LONG32P => {
+                let int = self.read_uint((tag - TYPE_LONG8P) / 2)?;
+                ArrayKey::Int(i64::from(int))
+            }
+            TYPE_LONG8N | TYPE_LONG16N | TYPE_LONG32N => {
+                let int = self.read_uint((tag - TYPE_LONG8N) / 2)?;
+                ArrayKey::Int(-i64::from(int))
+            }
+            TYPE_LONG64P => {
+                let int = self.read_u64()?;
+                ArrayKey::Int(i64::try_from(int).map_err(|_| Error::BadNumber(self.offset))?)
+            }
+            TYPE_LONG64N => {
+                let int = self.read_u64()?;
+                if int > i64::MIN.unsigned_abs() {
+                    return Err(Error::BadNumber(self.offset).into());
+                }
+                ArrayKey::Int(0i64.wrapping_sub_unsigned(int))
+            }
+            TYPE_STRING_EMPTY => ArrayKey::String(self.read_str(0)?),
+            TYPE_STRING_ID8 | TYPE_STRING_ID16 | TYPE_STRING_ID32 => {
+                ArrayKey::String(self.read_string_id(tag - TYPE_STRING_ID8)?)
+            }
+            TYPE_STRING8 | TYPE_STRING16 | TYPE_STRING32 => {
+                ArrayKey::String(self.read_string(tag - TYPE_STRING8)?)
+            }
+            _ => return Err(Error::BadArrayKeyType(self.offset).into()),
+        };
+        Ok(key)
+    }
+
+    /// Reads a string with a length of the specified width
+    /// and adds it to the string table.
+    fn read_string(&mut self, width: u8) -> IoResult<S> {
+        let len = self.read_size(width)?;
+        self.limits.string(len, self.offset)?;
+        let start = self.offset;
+        let string = self.read_str(len)?;
+        self.strings.push((start, self.offset));
+        Ok(string)
+    }
+
+    /// Reads a string id of the specified width and returns the string.
+    fn read_string_id(&mut self, width: u8) -> IoResult<S> {
+        let id = self.read_size(width)?;
+        let &(start, end) = self.strings.get(id).ok_or(Error::BadToken(self.offset))?;
+        self.limits.string(end - start, self.offset)?;
+        // the range was read by `read_str` before
+        Ok(unsafe { self.source.range(start, end) })
+    }
+
+    /// Reads a reference id of the specified width and returns the referenced slot.
+    fn read_ref(&mut self, width: u8) -> IoResult<usize> {
+        let id = self.read_size(width)?;
+        Ok(*self.ids.get(id).ok_or(Error::BadReference(id))?)
+    }
+
+    /// Reads the number of entries of an array.
+    ///
+    /// Each entry takes at least two bytes,
+    /// so a larger number than the remaining input is treated as truncated input.
+    fn read_len(&mut self, width: u8) -> IoResult<usize> {
+        let len = self.read_size(width)?;
+        if len > self.source.len() - self.offset {
+            return Err(Error::UnexpectedEof.into());
+        }
+        Ok(len)
+    }
+
+    fn read_str(&mut self, len: usize) -> IoResult<S> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.source.len())
+            .ok_or(Error::UnexpectedEof)?;
+        if !(self.source.is_boundary(self.offset) && self.source.is_boundary(end)) {
+            return Err(Error::BadEncoding(self.offset).into());
+        }
+        // boundaries checked above
+        let string = unsafe { self.source.range(self.offset, end) };
+        self.offset = end;
+        Ok(string)
+    }
+
+    /// Reads a big-endian unsigned integer of 1, 2 or 4 bytes for `width` 0, 1 or 2.
+    fn read_uint(&mut self, width: u8) -> IoResult<u32> {
+        let bytes = self.read_bytes(1 << width)?;
+        Ok(bytes
+            .iter()
+            .fold(0, |acc, &byte| acc << 8 | u32::from(byte)))
+    }
+
+    fn read_size(&mut self, width: u8) -> IoResult<usize> {
+        let size = self.read_uint(width)?;
+        Ok(usize::try_from(size).map_err(|_| Error::UnexpectedEof)?)
+    }
+
+    fn read_u8(&mut self) -> IoResult<u8> {
+        Ok(self.read_uint(0)?.to_be_bytes()[3])
+    }
+
+    fn read_u32(&mut self) -> IoResult<u32> {
+        self.read_uint(2)
+    }
+
+    fn read_u64(&mut self) -> IoResult<u64> {
+        let bytes = self.read_bytes(8)?;
+        Ok(bytes
+            .iter()
+            .fold(0, |acc, &byte| acc << 8 | u64::from(byte)))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&[u8]> {
+        let bytes = self
+            .source
+            .as_bytes()
+            .get(self.offset..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(Error::UnexpectedEof)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+}
+
+impl Frame {
+    fn new(kind: FrameKind, remaining: usize) -> Self {
+        Self {
+            kind,
+            remaining,
+            key_read: false,
+        }
+    }
+}
+
+/// Collects the slots referenced by `R:` references in `value`.
+fn collect_targets<'de, S: Str<'de>>(value: &Value<S>, targets: &mut HashSet<usize>) {
+    match value {
+        Value::Array(entries) => {
+            for (_, item) in entries {
+                collect_targets(item, targets);
+            }
+        }
+        Value::Object(object) => {
+            for (_, property) in object.properties() {
+                collect_targets(property, targets);
+            }
+        }
+        Value::Reference(r#ref) if r#ref.kind() == RefKind::Value => {
+            let _ = targets.insert(r#ref.index());
+        }
+        _ => {}
+    }
+}
+
+/// Encodes a `Value` in the igbinary format.
+struct Encoder<'t, W> {
+    write: W,
+    /// The id of each string written so far
+    strings: HashMap<Cow<'t, [u8]>, usize>,
+    /// The slots that are referenced by `R:` references
+    targets: HashSet<usize>,
+    /// The slot of the last value
+    slot: usize,
+    /// The reference id of the value in each slot, if any
+    ids: Vec<Option<usize>>,
+    next_id: usize,
+}
+
+impl<'t, W: Write> Encoder<'t, W> {
+    fn write_value<'de, S: Str<'de>>(&mut self, value: &'t Value<S>) -> io::Result<()> {
+        if let Value::Reference(r#ref) = value {
+            let id = self
+                .ids
+                .get(r#ref.index())
+                .copied()
+                .flatten()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "reference to a slot that cannot be referenced in igbinary",
+                    )
+                })?;
+            return match r#ref.kind() {
+                RefKind::Value => self.write_sized(TYPE_REF8, id),
+                RefKind::Object => {
+                    // `r:` takes a slot, which refers to the same object
+                    self.slot += 1;
+                    self.ids.push(Some(id));
+                    self.write_sized(TYPE_OBJREF8, id)
+                }
+            };
+        }
+
+        self.slot += 1;
+        let container = matches!(
+            value,
+            Value::Array(_) | Value::Object(_) | Value::Serializable(_)
+        );
+        if self.targets.contains(&self.slot) {
+            self.write.write_all(&[TYPE_REF])?;
+        } else if !container {
+            self.ids.push(None);
+        }
+        if container || self.targets.contains(&self.slot) {
+            self.ids.push(Some(self.next_id));
+            self.next_id += 1;
+        }
+
+        match value {
+            Value::Null => self.write.write_all(&[TYPE_NULL]),
+            Value::Bool(false) => self.write.write_all(&[TYPE_BOOL_FALSE]),
+            Value::Bool(true) => self.write.write_all(&[TYPE_BOOL_TRUE]),
+            Value::Int(int) => self.write_int(*int),
+            Value::Float(float) => {
+                self.write.write_all(&[TYPE_DOUBLE])?;
+                self.write.write_all(&float.to_bits().to_be_bytes())
+            }
+            Value::String(string) => self.write_string(Cow::Borrowed(string.as_bytes())),
+            Value::Array(entries) => {
+                self.write_sized(TYPE_ARRAY8, entries.len())?;
+                for (key, item) in entries {
+                    match key {
+                        ArrayKey::Int(int) => self.write_int(*int)?,
+                        ArrayKey::String(string) => {
+                            self.write_string(Cow::Borrowed(string.as_bytes()))?
+                        }
+                    }
+                    self.write_value(item)?;
+                }
+                Ok(())
+            }
+            Value::Object(object) => {
+                self.write_class(object.class().as_bytes())?;
+                self.write_sized(TYPE_ARRAY8, object.properties().len())?;
+                for (name, property) in object.properties() {
+                    self.write_string(mangle_property_name(name))?;
+                    self.write_value(property)?;
+                }
+                Ok(())
+            }
+            Value::Serializable(ser) => {
+                self.write_class(ser.class().as_bytes())?;
+                let data = ser.data().as_bytes();
+                self.write_sized(TYPE_OBJECT_SER8, data.len())?;
+                self.write.write_all(data)
+            }
+            Value::Enum(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "enum cases are not supported in igbinary",
+            )),
+            Value::Reference(_) => unreachable!("references are handled above"),
+        }
+    }
+
+    fn write_int(&mut self, int: i64) -> io::Result<()> {
+        let abs = int.unsigned_abs();
+        let tag = match abs {
+            0..=0xff => TYPE_LONG8P,
+            0x100..=0xffff => TYPE_LONG16P,
+            0x1_0000..=0xffff_ffff => TYPE_LONG32P,
+            _ => TYPE_LONG64P,
+        };
+        let bytes = abs.to_be_bytes();
+        let (tag, bytes) = match tag {
+            TYPE_LONG64P => (tag, &bytes[..]),
+            _ => {
+                let width = usize::from(tag - TYPE_LONG8P) / 2;
+                (
+                    tag,
+                    bytes.get(8 - (1 << width)..).expect("width is at most 2"),
+                )
+            }
+        };
+        let tag = if int < 0 { tag + 1 } else { tag };
+        self.write.write_all(&[tag])?;
+        self.write.write_all(bytes)
+    }
+
+    /// Writes a string, or its id if the same string was written before.
+    fn write_string(&mut self, string: Cow<'t, [u8]>) -> io::Result<()> {
+        if string.is_empty() {
+            return self.write.write_all(&[TYPE_STRING_EMPTY]);
+        }
+        if let Some(&id) = self.strings.get(&string) {
+            return self.write_sized(TYPE_STRING_ID8, id);
+        }
+        self.write_sized(TYPE_STRING8, string.len())?;
+        self.write.write_all(&string)?;
+        let id = self.strings.len();
+        let _ = self.strings.insert(string, id);
+        Ok(())
+    }
+
+    /// Writes a class name, or its id if the same string was written before.
+    fn write_class(&mut self, class: &'t [u8]) -> io::Result<()> {
+        if let Some(&id) = self.strings.get(class) {
+            return self.write_sized(TYPE_OBJECT_ID8, id);
+        }
+        self.write_sized(TYPE_OBJECT8, class.len())?;
+        self.write.write_all(class)?;
+        let id = self.strings.len();
+        let _ = self.strings.insert(Cow::Borrowed(class), id);
+        Ok(())
+    }
+
+    /// Writes the 8-bit variant `tag8` of a type or its 16-bit or 32-bit variant,
+    /// followed by `size` in the corresponding width.
+    fn write_sized(&mut self, tag8: u8, size: usize) -> io::Result<()> {
+        let size = u32::try_from(size)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "size exceeds 32 bits"))?;
+        let bytes = size.to_be_bytes();
+        let (tag, bytes) = match size {
+            0..=0xff => (tag8, &bytes[3..]),
+            0x100..=0xffff => (tag8 + 1, &bytes[2..]),
+            _ => (tag8 + 2, &bytes[..]),
+        };
+        self.write.write_all(&[tag])?;
+        self.write.write_all(bytes)
+    }
+}
+
+/// Returns the name of a property with the visibility mangling of PHP.
+fn mangle_property_name<'t, 'de, S: Str<'de>>(name: &'t PropertyName<S>) -> Cow<'t, [u8]> {
+    let bytes = name.name().as_bytes();
+    match name.vis() {
+        PropertyVis::Public => Cow::Borrowed(bytes),
+        PropertyVis::Protected => Cow::Owned([&b"\0*\0"[..], bytes].concat()),
+        PropertyVis::Private(class) => {
+            Cow::Owned([&b"\0"[..], class.as_bytes(), b"\0", bytes].concat())
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+
+use crate::parse::{read_value_tagged, Limits};
+use crate::*;
+
+/// An iterator over values serialized back-to-back in one source,
+/// such as a log of `serialize()` records without delimiters.
+///
+/// The iterator ends when the source ends between two values.
+/// If the source ends in the middle of a value,
+/// `Error::UnexpectedEof` is yielded instead.
+/// The iterator ends after yielding any error,
+/// since the position of the next value is unknown.
+pub struct Values<'de, S: Str<'de>, Src: Source<'de, S>> {
+    source: Src,
+    options: ParseOptions,
+    done: bool,
+    _ph: PhantomData<&'de S>,
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> Values<'de, S, Src> {
+    /// Creates an iterator over the values in `source`.
+    pub fn new(source: Src) -> Self {
+        Self::with_options(source, &ParseOptions::default())
+    }
+
+    /// Creates an iterator over the values in `source` with the specified limits.
+    ///
+    /// The limits apply to each value separately.
+    pub fn with_options(source: Src, options: &ParseOptions) -> Self {
+        Self {
+            source,
+            options: options.clone(),
+            done: false,
+            _ph: PhantomData,
+        }
+    }
+
+    /// Consumes the iterator and returns the underlying source.
+    pub fn into_source(self) -> Src {
+        self.source
+    }
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> Iterator for Values<'de, S, Src> {
+    type Item = IoResult<Value<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let tag = match self.source.read_u8_char() {
+            Ok(tag) => tag,
+            Err(IoError::Phpser(Error::UnexpectedEof)) => {
+                self.done = true;
+                return None;
+            }
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+        let mut limits = Limits::new(self.options.clone());
+        let result = read_value_tagged(&mut self.source, tag, &mut limits);
+        if result.is_err() {
+            self.done = true;
+        }
+        Some(result)
+    }
+}
+
+impl<'de, S: Str<'de>, Src: Source<'de, S>> FusedIterator for Values<'de, S, Src> {}
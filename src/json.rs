@@ -0,0 +1,1250 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use crate::emit::format_float;
+use crate::*;
+
+/// The alphabet of the standard base64 encoding.
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Options for converting values to JSON,
+/// corresponding to the flags and the depth of PHP's `json_encode`.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonOptions {
+    /// Indents the output with 4 spaces like `JSON_PRETTY_PRINT`.
+    pub pretty_print: bool,
+    /// Writes `/` as-is instead of `\/`, like `JSON_UNESCAPED_SLASHES`.
+    pub unescaped_slashes: bool,
+    /// Writes non-ASCII characters as-is instead of `\uXXXX`, like `JSON_UNESCAPED_UNICODE`.
+    ///
+    /// U+2028 and U+2029 are still escaped, like PHP does by default.
+    pub unescaped_unicode: bool,
+    /// Writes integral floats as `1.0` instead of `1`, like `JSON_PRESERVE_ZERO_FRACTION`.
+    pub preserve_zero_fraction: bool,
+    /// The handling of strings that are not valid UTF-8.
+    pub invalid_utf8: InvalidUtf8,
+    /// The format of floats, corresponding to the `serialize_precision` ini setting of PHP.
+    ///
+    /// The annotated format ignores this and always uses `FloatPrecision::Shortest`,
+    /// which reads back as the same float.
+    pub float_precision: FloatPrecision,
+    /// The maximum nesting depth of arrays and objects, like the `depth` parameter.
+    ///
+    /// A top-level array has a depth of 1.
+    /// Defaults to 512, the same as PHP.
+    pub max_depth: usize,
+    /// Writes the lossless annotated format instead of the output of `json_encode`.
+    ///
+    /// The annotated format keeps everything that `json_encode` discards,
+    /// so that `Value::from_annotated_json` can convert the JSON back to the same `Value`:
+    ///
+    /// - Null, bools, ints and UTF-8 strings are written as JSON values.
+    /// - Floats are written in the shortest form that reads back as the same float,
+    ///   and always have a fraction or an exponent;
+    ///   NAN and infinities are written as `{"$float": "NAN"}`, `{"$float": "INF"}` and `{"$float": "-INF"}`.
+    /// - Strings that are not valid UTF-8 are written as `{"$base64": "..."}`
+    ///   if `invalid_utf8` is `InvalidUtf8::Base64`.
+    ///   This also applies to array keys, class names and property names.
+    /// - Arrays are written as `{"$array": [[key, value], ...]}`,
+    ///   where `key` is an int or a string.
+    /// - Objects are written as `{"$object": class, "properties": [property, ...]}`,
+    ///   where each property is `{"name": name, "value": value}`
+    ///   with `"visibility": "protected"` or `"visibility": "private", "class": class`
+    ///   for non-public properties.
+    ///   Incomplete objects additionally have `"incomplete": true`.
+    /// - `Serializable` objects are written as `{"$serializable": class, "data": data}`.
+    /// - Enum cases are written as `{"$enum": class, "case": case}`.
+    /// - References are written as `{"$ref": slot}` for `R:` and `{"$objref": slot}` for `r:`
+    ///   instead of being resolved.
+    pub annotated: bool,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self {
+            pretty_print: false,
+            unescaped_slashes: false,
+            unescaped_unicode: false,
+            preserve_zero_fraction: false,
+            invalid_utf8: InvalidUtf8::default(),
+            float_precision: FloatPrecision::default(),
+            max_depth: 512,
+            annotated: false,
+        }
+    }
+}
+
+/// The handling of strings that are not valid UTF-8 when converting to JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidUtf8 {
+    /// Fails with an `io::ErrorKind::InvalidData` error, like `json_encode` does by default.
+    #[default]
+    Error,
+    /// Replaces invalid sequences with U+FFFD, like `JSON_INVALID_UTF8_SUBSTITUTE`.
+    Substitute,
+    /// Writes the base64 encoding of the whole string.
+    ///
+    /// Without `JsonOptions::annotated`,
+    /// the result cannot be distinguished from a string that contained the base64 text.
+    Base64,
+}
+
+impl<'de, S: Str<'de>> Value<S> {
+    /// Converts this value to JSON like `json_encode` does.
+    ///
+    /// # Errors
+    /// See `write_json_with`.
+    pub fn to_json(&self) -> io::Result<String> {
+        self.to_json_with(&JsonOptions::default())
+    }
+
+    /// Converts this value to JSON with the specified options.
+    ///
+    /// # Errors
+    /// See `write_json_with`.
+    pub fn to_json_with(&self, options: &JsonOptions) -> io::Result<String> {
+        let mut buf = vec![];
+        self.write_json_with(&mut buf, options)?;
+        Ok(String::from_utf8(buf).expect("JSON output is always valid UTF-8"))
+    }
+
+    /// Writes this value to `write` as JSON like `json_encode` does.
+    ///
+    /// The output is not buffered;
+    /// consider wrapping `write` with an `io::BufWriter`.
+    pub fn write_json(&self, write: impl Write) -> io::Result<()> {
+        self.write_json_with(write, &JsonOptions::default())
+    }
+
+    /// Writes this value to `write` as JSON with the specified options.
+    ///
+    /// Like `json_encode`, arrays with the keys `0, 1, 2, ...` in order become JSON arrays,
+    /// other arrays become JSON objects,
+    /// objects only expose their public properties,
+    /// and references are resolved to their targets.
+    /// Incomplete objects expose the `__PHP_Incomplete_Class_Name` property like PHP does.
+    /// The properties of `Serializable` objects are not available,
+    /// so they are written as `{}`.
+    ///
+    /// # Errors
+    /// Unlike `json_encode` with `JSON_PARTIAL_OUTPUT_ON_ERROR`,
+    /// this fails instead of writing a placeholder, and part of the output may have been written.
+    /// Returns an `io::ErrorKind::InvalidData` error
+    /// if a string is not valid UTF-8 and `invalid_utf8` is `InvalidUtf8::Error`.
+    /// Returns an `io::ErrorKind::InvalidInput` error if the value is nested deeper than `max_depth`,
+    /// or, without `JsonOptions::annotated`, if it contains NAN or an infinity, an enum case,
+    /// a reference to a slot that does not exist, or a recursive reference.
+    /// The backing values of enums are not stored in serialized data,
+    /// so enum cases cannot be converted like `json_encode` does.
+    pub fn write_json_with(&self, write: impl Write, options: &JsonOptions) -> io::Result<()> {
+        let mut slots = vec![];
+        if !options.annotated {
+            collect_slots(self, &mut slots);
+        }
+        let mut encoder = JsonEncoder {
+            write,
+            options,
+            slots,
+            stack: vec![],
+            indent: 0,
+        };
+        encoder.write_value(self)
+    }
+}
+
+/// Collects the value in each slot of `value`, with `r:` references resolved.
+fn collect_slots<'v, 'de, S: Str<'de>>(value: &'v Value<S>, slots: &mut Vec<&'v Value<S>>) {
+    match value {
+        Value::Reference(r#ref) if r#ref.kind() == RefKind::Value => {}
+        Value::Reference(r#ref) => {
+            let target = r#ref
+                .index()
+                .checked_sub(1)
+                .and_then(|index| slots.get(index))
+                .copied()
+                .unwrap_or(value);
+            slots.push(target);
+        }
+        Value::Array(entries) => {
+            slots.push(value);
+            for (_, item) in entries {
+                collect_slots(item, slots);
+            }
+        }
+        Value::Object(object) => {
+            slots.push(value);
+            for (_, property) in object.properties() {
+                collect_slots(property, slots);
+            }
+        }
+        _ => slots.push(value),
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+struct JsonEncoder<'o, 'v, W, S> {
+    write: W,
+    options: &'o JsonOptions,
+    /// The value in each slot, used to resolve references in the plain format
+    slots: Vec<&'v Value<S>>,
+    /// The arrays and objects being written, outermost first
+    stack: Vec<&'v Value<S>>,
+    /// The nesting level of JSON arrays and objects, used for pretty printing
+    indent: usize,
+}
+
+impl<'o, 'v, 'de, W: Write, S: Str<'de>> JsonEncoder<'o, 'v, W, S> {
+    fn write_value(&mut self, value: &'v Value<S>) -> io::Result<()> {
+        if self.options.annotated {
+            return self.write_annotated(value);
+        }
+
+        match value {
+            Value::Null => self.write.write_all(b"null"),
+            Value::Bool(bool) => self.write_bool(*bool),
+            Value::Int(int) => write!(self.write, "{}", int),
+            Value::Float(float) => {
+                if !float.is_finite() {
+                    return Err(invalid_input(
+                        "NAN and infinities cannot be written as JSON",
+                    ));
+                }
+                self.write_float(*float, self.options.preserve_zero_fraction)
+            }
+            Value::String(string) => self.write_string(string.as_bytes()),
+            Value::Array(entries) => {
+                let is_list = entries.iter().enumerate().all(|(index, (key, _))| {
+                    matches!(key, ArrayKey::Int(int) if i64::try_from(index) == Ok(*int))
+                });
+                self.enter(value)?;
+                if is_list {
+                    self.open(b"[")?;
+                    for (index, (_, item)) in entries.iter().enumerate() {
+                        self.write_separator(index)?;
+                        self.write_value(item)?;
+                    }
+                    self.close(!entries.is_empty(), b"]")?;
+                } else {
+                    self.open(b"{")?;
+                    for (index, (key, item)) in entries.iter().enumerate() {
+                        self.write_separator(index)?;
+                        match key {
+                            ArrayKey::Int(int) => write!(self.write, "\"{}\"", int)?,
+                            ArrayKey::String(string) => self.write_string(string.as_bytes())?,
+                        }
+                        self.write_colon()?;
+                        self.write_value(item)?;
+                    }
+                    self.close(!entries.is_empty(), b"}")?;
+                }
+                self.leave();
+                Ok(())
+            }
+            Value::Object(object) => {
+                self.enter(value)?;
+                self.open(b"{")?;
+                let mut count = 0;
+                if object.incomplete() {
+                    self.write_separator(count)?;
+                    self.write_string(INCOMPLETE_CLASS_NAME.as_bytes())?;
+                    self.write_colon()?;
+                    self.write_string(object.class().as_bytes())?;
+                    count += 1;
+                }
+                for (name, property) in object.properties() {
+                    if let PropertyVis::Public = name.vis() {
+                        self.write_separator(count)?;
+                        self.write_string(name.name().as_bytes())?;
+                        self.write_colon()?;
+                        self.write_value(property)?;
+                        count += 1;
+                    }
+                }
+                self.close(count > 0, b"}")?;
+                self.leave();
+                Ok(())
+            }
+            Value::Serializable(_) => self.write.write_all(b"{}"),
+            Value::Enum(_) => Err(invalid_input("enum cases cannot be written as JSON")),
+            Value::Reference(r#ref) => {
+                let target = r#ref
+                    .index()
+                    .checked_sub(1)
+                    .and_then(|index| self.slots.get(index))
+                    .copied()
+                    .filter(|target| !matches!(target, Value::Reference(_)))
+                    .ok_or_else(|| invalid_input("reference to a slot that does not exist"))?;
+                self.write_value(target)
+            }
+        }
+    }
+
+    fn write_annotated(&mut self, value: &'v Value<S>) -> io::Result<()> {
+        match value {
+            Value::Null => self.write.write_all(b"null"),
+            Value::Bool(bool) => self.write_bool(*bool),
+            Value::Int(int) => write!(self.write, "{}", int),
+            Value::Float(float) if float.is_finite() => self.write_float(*float, true),
+            Value::Float(float) => {
+                let name = if float.is_nan() {
+                    "NAN"
+                } else if *float > 0. {
+                    "INF"
+                } else {
+                    "-INF"
+                };
+                self.open(b"{")?;
+                self.write_field(0, "$float", |this| this.write_string(name.as_bytes()))?;
+                self.close(true, b"}")
+            }
+            Value::String(string) => self.write_string(string.as_bytes()),
+            Value::Array(entries) => {
+                self.enter(value)?;
+                self.open(b"{")?;
+                self.write_field(0, "$array", |this| {
+                    this.open(b"[")?;
+                    for (index, (key, item)) in entries.iter().enumerate() {
+                        this.write_separator(index)?;
+                        this.open(b"[")?;
+                        this.write_separator(0)?;
+                        match key {
+                            ArrayKey::Int(int) => write!(this.write, "{}", int)?,
+                            ArrayKey::String(string) => this.write_string(string.as_bytes())?,
+                        }
+                        this.write_separator(1)?;
+                        this.write_value(item)?;
+                        this.close(true, b"]")?;
+                    }
+                    this.close(!entries.is_empty(), b"]")
+                })?;
+                self.close(true, b"}")?;
+                self.leave();
+                Ok(())
+            }
+            Value::Object(object) => {
+                self.enter(value)?;
+                self.open(b"{")?;
+                self.write_field(0, "$object", |this| {
+                    this.write_string(object.class().as_bytes())
+                })?;
+                let mut count = 1;
+                if object.incomplete() {
+                    self.write_field(count, "incomplete", |this| this.write_bool(true))?;
+                    count += 1;
+                }
+                self.write_field(count, "properties", |this| {
+                    this.open(b"[")?;
+                    for (index, (name, property)) in object.properties().iter().enumerate() {
+                        this.write_separator(index)?;
+                        this.write_annotated_property(name, property)?;
+                    }
+                    this.close(!object.properties().is_empty(), b"]")
+                })?;
+                self.close(true, b"}")?;
+                self.leave();
+                Ok(())
+            }
+            Value::Serializable(ser) => {
+                self.open(b"{")?;
+                self.write_field(0, "$serializable", |this| {
+                    this.write_string(ser.class().as_bytes())
+                })?;
+                self.write_field(1, "data", |this| this.write_string(ser.data().as_bytes()))?;
+                self.close(true, b"}")
+            }
+            Value::Enum(case) => {
+                self.open(b"{")?;
+                self.write_field(0, "$enum", |this| {
+                    this.write_string(case.class().as_bytes())
+                })?;
+                self.write_field(1, "case", |this| this.write_string(case.case().as_bytes()))?;
+                self.close(true, b"}")
+            }
+            Value::Reference(r#ref) => {
+                let name = match r#ref.kind() {
+                    RefKind::Value => "$ref",
+                    RefKind::Object => "$objref",
+                };
+                self.open(b"{")?;
+                self.write_field(0, name, |this| write!(this.write, "{}", r#ref.index()))?;
+                self.close(true, b"}")
+            }
+        }
+    }
+
+    fn write_annotated_property(
+        &mut self,
+        name: &'v PropertyName<S>,
+        property: &'v Value<S>,
+    ) -> io::Result<()> {
+        self.open(b"{")?;
+        self.write_field(0, "name", |this| this.write_string(name.name().as_bytes()))?;
+        let count = match name.vis() {
+            PropertyVis::Public => 1,
+            PropertyVis::Protected => {
+                self.write_field(1, "visibility", |this| this.write_string(b"protected"))?;
+                2
+            }
+            PropertyVis::Private(class) => {
+                self.write_field(1, "visibility", |this| this.write_string(b"private"))?;
+                self.write_field(2, "class", |this| this.write_string(class.as_bytes()))?;
+                3
+            }
+        };
+        self.write_field(count, "value", |this| this.write_value(property))?;
+        self.close(true, b"}")
+    }
+
+    /// Writes the entry `"name": value` at `index` of an opened JSON object.
+    fn write_field(
+        &mut self,
+        index: usize,
+        name: &str,
+        value: impl FnOnce(&mut Self) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.write_separator(index)?;
+        self.write_escaped(name)?;
+        self.write_colon()?;
+        value(self)
+    }
+
+    /// Enters an array or object, checking the depth and recursion.
+    fn enter(&mut self, value: &'v Value<S>) -> io::Result<()> {
+        if self.stack.iter().any(|&outer| std::ptr::eq(outer, value)) {
+            return Err(invalid_input(
+                "recursive reference cannot be written as JSON",
+            ));
+        }
+        if self.stack.len() >= self.options.max_depth {
+            return Err(invalid_input("value is nested deeper than max_depth"));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// Leaves the innermost array or object.
+    fn leave(&mut self) {
+        let _ = self.stack.pop();
+    }
+
+    /// Opens a JSON array or object with `open`.
+    fn open(&mut self, open: &[u8]) -> io::Result<()> {
+        self.indent += 1;
+        self.write.write_all(open)
+    }
+
+    /// Closes a JSON array or object with `close`.
+    fn close(&mut self, non_empty: bool, close: &[u8]) -> io::Result<()> {
+        self.indent -= 1;
+        if self.options.pretty_print && non_empty {
+            self.write_indent()?;
+        }
+        self.write.write_all(close)
+    }
+
+    /// Writes the separator before the entry at `index` of an array or object.
+    fn write_separator(&mut self, index: usize) -> io::Result<()> {
+        if index > 0 {
+            self.write.write_all(b",")?;
+        }
+        if self.options.pretty_print {
+            self.write_indent()?;
+        }
+        Ok(())
+    }
+
+    fn write_indent(&mut self) -> io::Result<()> {
+        self.write.write_all(b"\n")?;
+        for _ in 0..self.indent {
+            self.write.write_all(b"    ")?;
+        }
+        Ok(())
+    }
+
+    fn write_colon(&mut self) -> io::Result<()> {
+        if self.options.pretty_print {
+            self.write.write_all(b": ")
+        } else {
+            self.write.write_all(b":")
+        }
+    }
+
+    fn write_bool(&mut self, bool: bool) -> io::Result<()> {
+        self.write
+            .write_all(if bool { &b"true"[..] } else { &b"false"[..] })
+    }
+
+    fn write_float(&mut self, float: f64, zero_fraction: bool) -> io::Result<()> {
+        let precision = if self.options.annotated {
+            FloatPrecision::Shortest
+        } else {
+            self.options.float_precision
+        };
+        let mut string = format_float(float, precision).replace('E', "e");
+        if zero_fraction && !string.contains(['.', 'e']) {
+            string.push_str(".0");
+        }
+        self.write.write_all(string.as_bytes())
+    }
+
+    fn write_string(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if let Ok(string) = std::str::from_utf8(bytes) {
+            return self.write_escaped(string);
+        }
+        match self.options.invalid_utf8 {
+            InvalidUtf8::Error => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "string is not valid UTF-8",
+            )),
+            InvalidUtf8::Substitute => self.write_escaped(&String::from_utf8_lossy(bytes)),
+            InvalidUtf8::Base64 if self.options.annotated => {
+                self.open(b"{")?;
+                self.write_field(0, "$base64", |this| {
+                    this.write_escaped(&encode_base64(bytes))
+                })?;
+                self.close(true, b"}")
+            }
+            InvalidUtf8::Base64 => self.write_escaped(&encode_base64(bytes)),
+        }
+    }
+
+    /// Writes a JSON string literal in the same way as `php_json_escape_string`.
+    fn write_escaped(&mut self, string: &str) -> io::Result<()> {
+        let mut out = String::with_capacity(string.len() + 2);
+        out.push('"');
+        for char in string.chars() {
+            match char {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '/' if !self.options.unescaped_slashes => out.push_str("\\/"),
+                '\u{8}' => out.push_str("\\b"),
+                '\u{c}' => out.push_str("\\f"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                '\0'..='\u{1f}' | '\u{2028}' | '\u{2029}' => push_unicode_escape(&mut out, char),
+                '\u{80}'.. if !self.options.unescaped_unicode => {
+                    push_unicode_escape(&mut out, char)
+                }
+                _ => out.push(char),
+            }
+        }
+        out.push('"');
+        self.write.write_all(out.as_bytes())
+    }
+}
+
+/// Appends `char` as `\uXXXX` escapes of its UTF-16 code units.
+fn push_unicode_escape(out: &mut String, char: char) {
+    let mut units = [0; 2];
+    for unit in char.encode_utf16(&mut units) {
+        out.push_str(&format!("\\u{:04x}", unit));
+    }
+}
+
+/// Encodes `bytes` in the standard base64 encoding with padding.
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut buf = [0; 3];
+        for (dest, &byte) in buf.iter_mut().zip(chunk) {
+            *dest = byte;
+        }
+        let bits = u32::from(buf[0]) << 16 | u32::from(buf[1]) << 8 | u32::from(buf[2]);
+        for (index, shift) in [18, 12, 6, 0].iter().enumerate() {
+            if index <= chunk.len() {
+                let sextet = usize::try_from((bits >> shift) & 0x3f).expect("6 bits");
+                let digit = BASE64_ALPHABET.get(sextet).expect("6 bits index 64 digits");
+                out.push(char::from(*digit));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Options for converting JSON to values,
+/// corresponding to the parameters of PHP's `json_decode`.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonDecodeOptions {
+    /// Converts JSON objects to arrays instead of `stdClass` objects,
+    /// like the `associative` parameter.
+    pub assoc: bool,
+    /// Converts integers that do not fit in an `int` to strings instead of floats,
+    /// like `JSON_BIGINT_AS_STRING`.
+    pub bigint_as_string: bool,
+    /// The maximum nesting depth of arrays and objects, like the `depth` parameter.
+    ///
+    /// A top-level array has a depth of 1.
+    /// Defaults to 512, the same as PHP.
+    pub max_depth: usize,
+}
+
+impl Default for JsonDecodeOptions {
+    fn default() -> Self {
+        Self {
+            assoc: false,
+            bigint_as_string: false,
+            max_depth: 512,
+        }
+    }
+}
+
+impl Value<String> {
+    /// Converts JSON to a value like `json_decode($json, $assoc)` does.
+    ///
+    /// # Errors
+    /// See `from_json_with`.
+    pub fn from_json(json: &str, assoc: bool) -> Result<Self> {
+        Self::from_json_with(
+            json,
+            &JsonDecodeOptions {
+                assoc,
+                ..JsonDecodeOptions::default()
+            },
+        )
+    }
+
+    /// Converts JSON to a value with the specified options.
+    ///
+    /// JSON arrays become arrays with the keys `0, 1, 2, ...`.
+    /// JSON objects become arrays if `assoc` is true and `stdClass` objects otherwise.
+    /// Like PHP, array keys that are canonical decimal integers become int keys,
+    /// and a repeated key replaces the value of its first occurrence.
+    /// Integers that do not fit in an `int` become floats unless `bigint_as_string` is true.
+    ///
+    /// # Errors
+    /// Returns an error if the input is not valid JSON,
+    /// which includes unescaped control characters and unpaired UTF-16 surrogates.
+    /// Returns `Error::DepthLimitExceeded` if the input is nested deeper than `max_depth`.
+    /// If `assoc` is false,
+    /// returns `Error::BadToken` if a property name starts with `\0` like PHP does.
+    pub fn from_json_with(json: &str, options: &JsonDecodeOptions) -> Result<Self> {
+        let node = JsonParser::new(json, options.max_depth).parse_document()?;
+        node.into_value(options)
+    }
+}
+
+impl Value<Vec<u8>> {
+    /// Converts JSON in the annotated format of `JsonOptions::annotated` back to a value.
+    ///
+    /// # Errors
+    /// See `from_annotated_json_with`.
+    pub fn from_annotated_json(json: &str) -> Result<Self> {
+        Self::from_annotated_json_with(json, &JsonDecodeOptions::default())
+    }
+
+    /// Converts JSON in the annotated format of `JsonOptions::annotated` back to a value
+    /// with the specified limits.
+    ///
+    /// Only `max_depth` is used, which limits the nesting of arrays and objects
+    /// rather than the nesting of the JSON.
+    ///
+    /// # Errors
+    /// Returns an error if the input is not valid JSON,
+    /// `Error::BadToken` if the JSON does not follow the annotated format
+    /// or a `$base64` string is not valid base64,
+    /// and `Error::BadNumber` if an int is out of range.
+    pub fn from_annotated_json_with(json: &str, options: &JsonDecodeOptions) -> Result<Self> {
+        // each array or object takes at most 3 levels of JSON, plus 1 for a `$base64` name
+        let max_json_depth = options.max_depth.saturating_mul(3).saturating_add(1);
+        let node = JsonParser::new(json, max_json_depth).parse_document()?;
+        node.into_annotated(0, options.max_depth)
+    }
+}
+
+/// A parsed JSON value.
+struct Json<'j> {
+    /// The offset of the first byte of the value
+    offset: usize,
+    kind: JsonKind<'j>,
+}
+
+enum JsonKind<'j> {
+    Null,
+    Bool(bool),
+    /// The number as written, which is validated by the parser
+    Number(&'j str),
+    String(String),
+    Array(Vec<Json<'j>>),
+    /// The offset of each key, the key and the value
+    Object(Vec<(usize, String, Json<'j>)>),
+}
+
+impl<'j> Json<'j> {
+    /// The error for a value that does not match the expected shape.
+    fn bad_token(&self) -> Error {
+        Error::BadToken(self.offset + 1)
+    }
+
+    fn into_value(self, options: &JsonDecodeOptions) -> Result<Value<String>> {
+        let value = match self.kind {
+            JsonKind::Null => Value::Null,
+            JsonKind::Bool(bool) => Value::Bool(bool),
+            JsonKind::Number(number) => {
+                let is_int = !number.contains(['.', 'e', 'E']);
+                match number.parse() {
+                    Ok(int) if is_int => Value::Int(int),
+                    _ if is_int && options.bigint_as_string => Value::String(number.to_owned()),
+                    _ => Value::Float(number.parse().expect("validated by the parser")),
+                }
+            }
+            JsonKind::String(string) => Value::String(string),
+            JsonKind::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, item)| {
+                        let key = i64::try_from(index).expect("array length fits in i64");
+                        Ok((ArrayKey::Int(key), item.into_value(options)?))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            JsonKind::Object(fields) => {
+                let fields = dedup_fields(fields);
+                if options.assoc {
+                    Value::Array(
+                        fields
+                            .into_iter()
+                            .map(|(_, key, item)| {
+                                Ok((ArrayKey::from_str_key(key), item.into_value(options)?))
+                            })
+                            .collect::<Result<_>>()?,
+                    )
+                } else {
+                    let properties = fields
+                        .into_iter()
+                        .map(|(offset, key, item)| {
+                            if key.starts_with('\0') {
+                                return Err(Error::BadToken(offset + 1));
+                            }
+                            let name = PropertyName::new(PropertyVis::Public, key);
+                            Ok((name, item.into_value(options)?))
+                        })
+                        .collect::<Result<_>>()?;
+                    Value::Object(Object::new("stdClass".to_owned(), properties))
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    fn into_annotated(self, depth: usize, max_depth: usize) -> Result<Value<Vec<u8>>> {
+        let offset = self.offset;
+        let bad_token = self.bad_token();
+        let value = match self.kind {
+            JsonKind::Null => Value::Null,
+            JsonKind::Bool(bool) => Value::Bool(bool),
+            JsonKind::Number(number) => {
+                if number.contains(['.', 'e', 'E']) {
+                    Value::Float(number.parse().expect("validated by the parser"))
+                } else {
+                    Value::Int(
+                        number
+                            .parse()
+                            .map_err(|_| Error::BadNumber(offset + number.len()))?,
+                    )
+                }
+            }
+            JsonKind::String(string) => Value::String(string.into_bytes()),
+            JsonKind::Array(_) => return Err(bad_token),
+            JsonKind::Object(fields) => {
+                let mut fields = Fields {
+                    fields,
+                    error: bad_token,
+                };
+                let tag = fields
+                    .fields
+                    .first()
+                    .map(|(_, tag, _)| tag.clone())
+                    .ok_or(bad_token)?;
+                let value = match tag.as_str() {
+                    "$float" => match fields.take_string("$float")?.as_slice() {
+                        b"NAN" => Value::Float(f64::NAN),
+                        b"INF" => Value::Float(f64::INFINITY),
+                        b"-INF" => Value::Float(f64::NEG_INFINITY),
+                        _ => return Err(bad_token),
+                    },
+                    "$base64" => {
+                        let text = fields.take_string("$base64")?;
+                        Value::String(decode_base64(&text).ok_or(bad_token)?)
+                    }
+                    "$array" => {
+                        let depth = enter_annotated(depth, max_depth, offset)?;
+                        let entries = fields
+                            .take_array("$array")?
+                            .into_iter()
+                            .map(|entry| {
+                                let malformed = entry.bad_token();
+                                let mut pair = match entry.kind {
+                                    JsonKind::Array(pair) if pair.len() == 2 => pair.into_iter(),
+                                    _ => return Err(malformed),
+                                };
+                                let key = pair.next().ok_or(malformed)?;
+                                let item = pair.next().ok_or(malformed)?;
+                                let key = match key.into_annotated(depth, max_depth)? {
+                                    Value::Int(int) => ArrayKey::Int(int),
+                                    Value::String(string) => ArrayKey::String(string),
+                                    _ => return Err(malformed),
+                                };
+                                Ok((key, item.into_annotated(depth, max_depth)?))
+                            })
+                            .collect::<Result<_>>()?;
+                        Value::Array(entries)
+                    }
+                    "$object" => {
+                        let depth = enter_annotated(depth, max_depth, offset)?;
+                        let class = fields.take_string("$object")?;
+                        let incomplete = match fields.take("incomplete") {
+                            None => false,
+                            Some(Json {
+                                kind: JsonKind::Bool(bool),
+                                ..
+                            }) => bool,
+                            Some(_) => return Err(bad_token),
+                        };
+                        let properties = fields
+                            .take_array("properties")?
+                            .into_iter()
+                            .map(|property| property.into_annotated_property(depth, max_depth))
+                            .collect::<Result<_>>()?;
+                        if incomplete {
+                            Value::Object(Object::new_incomplete(class, properties))
+                        } else {
+                            Value::Object(Object::new(class, properties))
+                        }
+                    }
+                    "$serializable" => {
+                        let class = fields.take_string("$serializable")?;
+                        let data = fields.take_string("data")?;
+                        Value::Serializable(Serializable::new(class, data))
+                    }
+                    "$enum" => {
+                        let class = fields.take_string("$enum")?;
+                        let case = fields.take_string("case")?;
+                        Value::Enum(EnumCase::new(class, case))
+                    }
+                    "$ref" | "$objref" => {
+                        let kind = if tag == "$ref" {
+                            RefKind::Value
+                        } else {
+                            RefKind::Object
+                        };
+                        let index =
+                            match fields.take(&tag).ok_or(bad_token)?.into_annotated(0, 0)? {
+                                Value::Int(int) => usize::try_from(int).map_err(|_| bad_token)?,
+                                _ => return Err(bad_token),
+                            };
+                        Value::Reference(Ref::with_kind(kind, index))
+                    }
+                    _ => return Err(bad_token),
+                };
+                fields.finish()?;
+                value
+            }
+        };
+        Ok(value)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn into_annotated_property(
+        self,
+        depth: usize,
+        max_depth: usize,
+    ) -> Result<(PropertyName<Vec<u8>>, Value<Vec<u8>>)> {
+        let bad_token = self.bad_token();
+        let mut fields = match self.kind {
+            JsonKind::Object(fields) => Fields {
+                fields,
+                error: bad_token,
+            },
+            _ => return Err(bad_token),
+        };
+        let name = fields.take_string("name")?;
+        let vis = match fields.take("visibility") {
+            None => PropertyVis::Public,
+            Some(visibility) => match visibility.kind {
+                JsonKind::String(visibility) if visibility == "protected" => PropertyVis::Protected,
+                JsonKind::String(visibility) if visibility == "private" => {
+                    PropertyVis::Private(fields.take_string("class")?)
+                }
+                _ => return Err(bad_token),
+            },
+        };
+        let value = fields
+            .take("value")
+            .ok_or(bad_token)?
+            .into_annotated(depth, max_depth)?;
+        fields.finish()?;
+        Ok((PropertyName::new(vis, name), value))
+    }
+}
+
+/// Enters an array or object of the annotated format, returning the new depth.
+fn enter_annotated(depth: usize, max_depth: usize, offset: usize) -> Result<usize> {
+    let depth = depth + 1;
+    if depth > max_depth {
+        return Err(Error::DepthLimitExceeded(offset + 1));
+    }
+    Ok(depth)
+}
+
+/// The fields of a JSON object in the annotated format.
+struct Fields<'j> {
+    fields: Vec<(usize, String, Json<'j>)>,
+    /// The error for a missing or malformed field
+    error: Error,
+}
+
+impl<'j> Fields<'j> {
+    fn take(&mut self, name: &str) -> Option<Json<'j>> {
+        let index = self.fields.iter().position(|(_, key, _)| key == name)?;
+        Some(self.fields.remove(index).2)
+    }
+
+    fn take_string(&mut self, name: &str) -> Result<Vec<u8>> {
+        let field = self.take(name).ok_or(self.error)?;
+        match field.kind {
+            JsonKind::String(string) => Ok(string.into_bytes()),
+            JsonKind::Object(_) => match field.into_annotated(0, 0) {
+                Ok(Value::String(string)) => Ok(string),
+                Ok(_) => Err(self.error),
+                Err(err) => Err(err),
+            },
+            _ => Err(self.error),
+        }
+    }
+
+    fn take_array(&mut self, name: &str) -> Result<Vec<Json<'j>>> {
+        match self.take(name).ok_or(self.error)?.kind {
+            JsonKind::Array(items) => Ok(items),
+            _ => Err(self.error),
+        }
+    }
+
+    /// Fails on the first field that has not been taken.
+    fn finish(self) -> Result {
+        match self.fields.first() {
+            Some((offset, _, _)) => Err(Error::BadToken(offset + 1)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Removes repeated keys of a JSON object,
+/// keeping the position of the first occurrence and the value of the last one like PHP.
+fn dedup_fields(fields: Vec<(usize, String, Json)>) -> Vec<(usize, String, Json)> {
+    let mut indices: HashMap<String, usize> = HashMap::with_capacity(fields.len());
+    let mut deduped: Vec<(usize, String, Json)> = Vec::with_capacity(fields.len());
+    for (offset, key, value) in fields {
+        match indices.get(&key) {
+            Some(&index) => {
+                if let Some(field) = deduped.get_mut(index) {
+                    field.2 = value;
+                }
+            }
+            None => {
+                let _ = indices.insert(key.clone(), deduped.len());
+                deduped.push((offset, key, value));
+            }
+        }
+    }
+    deduped
+}
+
+/// A recursive descent parser following the grammar of PHP's JSON parser.
+struct JsonParser<'j> {
+    input: &'j str,
+    offset: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'j> JsonParser<'j> {
+    fn new(input: &'j str, max_depth: usize) -> Self {
+        Self {
+            input,
+            offset: 0,
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    fn parse_document(mut self) -> Result<Json<'j>> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.offset < self.input.len() {
+            return Err(Error::TrailingData(self.offset));
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Json<'j>> {
+        self.skip_whitespace();
+        let offset = self.offset;
+        let kind = match self.peek().ok_or(Error::UnexpectedEof)? {
+            b'{' => self.parse_object()?,
+            b'[' => self.parse_array()?,
+            b'"' => JsonKind::String(self.parse_string()?),
+            b't' => {
+                self.expect_literal("true")?;
+                JsonKind::Bool(true)
+            }
+            b'f' => {
+                self.expect_literal("false")?;
+                JsonKind::Bool(false)
+            }
+            b'n' => {
+                self.expect_literal("null")?;
+                JsonKind::Null
+            }
+            b'-' | b'0'..=b'9' => JsonKind::Number(self.parse_number()?),
+            _ => return Err(Error::BadToken(offset + 1)),
+        };
+        Ok(Json { offset, kind })
+    }
+
+    fn parse_array(&mut self) -> Result<JsonKind<'j>> {
+        self.enter()?;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+        } else {
+            loop {
+                items.push(self.parse_value()?);
+                if self.expect_delimiter(b']')? {
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(JsonKind::Array(items))
+    }
+
+    fn parse_object(&mut self) -> Result<JsonKind<'j>> {
+        self.enter()?;
+        let mut fields = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let offset = self.offset;
+                match self.peek() {
+                    Some(b'"') => {}
+                    Some(_) => return Err(Error::BadToken(offset + 1)),
+                    None => return Err(Error::UnexpectedEof),
+                }
+                let key = self.parse_string()?;
+                self.skip_whitespace();
+                self.expect_byte(b':')?;
+                let value = self.parse_value()?;
+                fields.push((offset, key, value));
+                if self.expect_delimiter(b'}')? {
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(JsonKind::Object(fields))
+    }
+
+    /// Enters an array or object after its opening byte.
+    fn enter(&mut self) -> Result {
+        self.offset += 1;
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            return Err(Error::DepthLimitExceeded(self.offset));
+        }
+        Ok(())
+    }
+
+    /// Reads a `,` or the closing byte `close`, returning whether the closing byte was read.
+    fn expect_delimiter(&mut self, close: u8) -> Result<bool> {
+        self.skip_whitespace();
+        match self.next_byte()? {
+            b',' => Ok(false),
+            byte if byte == close => Ok(true),
+            _ => Err(Error::BadToken(self.offset)),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect_byte(b'"')?;
+        let mut string = String::new();
+        loop {
+            let rest = self.rest();
+            let run = rest
+                .iter()
+                .position(|&byte| byte == b'"' || byte == b'\\' || byte < 0x20)
+                .ok_or(Error::UnexpectedEof)?;
+            // the run ends before an ASCII byte
+            string.push_str(
+                self.input
+                    .get(self.offset..self.offset + run)
+                    .expect("run ends at a char boundary"),
+            );
+            self.offset += run;
+            match self.next_byte()? {
+                b'"' => return Ok(string),
+                b'\\' => {
+                    let char = match self.next_byte()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(Error::BadToken(self.offset)),
+                    };
+                    string.push(char);
+                }
+                _ => return Err(Error::BadToken(self.offset)),
+            }
+        }
+    }
+
+    /// Parses the hex digits of a `\u` escape, and the following escape for a surrogate pair.
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let start = self.offset - 2;
+        let unit = self.parse_hex4()?;
+        let code = match unit {
+            0xd800..=0xdbff => {
+                if self.rest().get(..2) != Some(b"\\u") {
+                    return Err(Error::BadToken(start + 1));
+                }
+                self.offset += 2;
+                let low = self.parse_hex4()?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(Error::BadToken(start + 1));
+                }
+                0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00)
+            }
+            0xdc00..=0xdfff => return Err(Error::BadToken(start + 1)),
+            _ => unit,
+        };
+        Ok(char::from_u32(code).expect("surrogates are handled above"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let mut unit = 0;
+        for _ in 0..4 {
+            let digit = char::from(self.next_byte()?)
+                .to_digit(16)
+                .ok_or(Error::BadToken(self.offset))?;
+            unit = unit << 4 | digit;
+        }
+        Ok(unit)
+    }
+
+    /// Parses a number, leaving its conversion to the caller.
+    fn parse_number(&mut self) -> Result<&'j str> {
+        let start = self.offset;
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+        match self.next_byte()? {
+            b'0' => {}
+            b'1'..=b'9' => self.skip_digits(),
+            _ => return Err(Error::BadNumber(self.offset)),
+        }
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            self.expect_digits()?;
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.offset += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.offset += 1;
+            }
+            self.expect_digits()?;
+        }
+        Ok(self
+            .input
+            .get(start..self.offset)
+            .expect("numbers are ASCII"))
+    }
+
+    fn expect_digits(&mut self) -> Result {
+        if !self.next_byte()?.is_ascii_digit() {
+            return Err(Error::BadNumber(self.offset));
+        }
+        self.skip_digits();
+        Ok(())
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str) -> Result {
+        for &expected in literal.as_bytes() {
+            self.expect_byte(expected)?;
+        }
+        Ok(())
+    }
+
+    fn expect_byte(&mut self, expected: u8) -> Result {
+        if self.next_byte()? != expected {
+            return Err(Error::BadToken(self.offset));
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn rest(&self) -> &'j [u8] {
+        self.input.as_bytes().get(self.offset..).unwrap_or_default()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.rest().first().copied()
+    }
+
+    fn next_byte(&mut self) -> Result<u8> {
+        let byte = self.peek().ok_or(Error::UnexpectedEof)?;
+        self.offset += 1;
+        Ok(byte)
+    }
+}
+
+/// Decodes the standard base64 encoding with padding.
+fn decode_base64(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let chunks = text.len() / 4;
+    let mut out = Vec::with_capacity(chunks * 3);
+    for (index, chunk) in text.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&byte| byte == b'=').count();
+        if padding > 2 || (padding > 0 && index + 1 < chunks) {
+            return None;
+        }
+        let mut bits = 0u32;
+        for &byte in chunk.iter().take(4 - padding) {
+            let sextet = BASE64_ALPHABET.iter().position(|&digit| digit == byte)?;
+            bits = bits << 6 | u32::try_from(sextet).expect("6 bits");
+        }
+        bits <<= 6 * u32::try_from(padding).expect("at most 2");
+        let bytes = bits.to_be_bytes();
+        out.extend(bytes.get(1..4 - padding).expect("at most 2 padding bytes"));
+    }
+    Some(out)
+}
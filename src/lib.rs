@@ -5,8 +5,7 @@
     variant_size_differences,
     clippy::checked_conversions,
     clippy::needless_borrow,
-    clippy::shadow_unrelated,
-    clippy::wrong_pub_self_convention
+    clippy::shadow_unrelated
 )]
 #![deny(
     anonymous_parameters,
@@ -16,8 +15,7 @@
     clippy::float_cmp_const,
     clippy::if_not_else,
     clippy::indexing_slicing,
-    clippy::option_unwrap_used,
-    clippy::result_unwrap_used
+    clippy::unwrap_used
 )]
 #![cfg_attr(
     debug_assertions,
@@ -46,5 +44,43 @@ pub use types::*;
 mod parse;
 pub use parse::*;
 
+mod event;
+pub use event::*;
+
+mod push;
+pub use push::*;
+
+#[cfg(feature = "tokio")]
+mod async_source;
+#[cfg(feature = "tokio")]
+pub use async_source::*;
+
 mod emit;
 pub use emit::*;
+
+mod graph;
+pub use graph::*;
+
+mod iter;
+pub use iter::*;
+
+mod diagnostic;
+pub use diagnostic::*;
+
+mod session;
+pub use session::*;
+
+mod igbinary;
+
+mod json;
+pub use json::*;
+
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "serde")]
+pub use de::*;
+
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "serde")]
+pub use ser::*;
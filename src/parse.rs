@@ -1,5 +1,8 @@
+use std::fmt;
 use std::str;
+use std::sync::Arc;
 
+use crate::event::{build_value, EventState};
 use crate::*;
 
 /// A stateful wrapper to make a `Str` a readable `Source`
@@ -13,6 +16,25 @@ impl<'de, S: Str<'de>> Cursor<S> {
     pub fn new(source: S) -> Self {
         Cursor { offset: 0, source }
     }
+
+    /// Returns the part of the `Str` that has not been read yet.
+    pub fn remaining(&self) -> S {
+        // `offset` only ever advances to boundaries within the source
+        unsafe { self.source.range_from(self.offset) }
+    }
+
+    /// Returns the whole input as bytes.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        self.source.as_bytes()
+    }
+
+    /// Returns the part of the input that has not been read yet as bytes, without copying.
+    pub(crate) fn remaining_bytes(&self) -> &[u8] {
+        self.source
+            .as_bytes()
+            .get(self.offset..)
+            .unwrap_or_default()
+    }
 }
 
 impl<'de, S: Str<'de>> Source<'de, S> for Cursor<S> {
@@ -25,7 +47,7 @@ impl<'de, S: Str<'de>> Source<'de, S> for Cursor<S> {
     }
 
     fn read_u8_char(&mut self) -> IoResult<u8> {
-        if self.offset + 1 >= self.source.len() {
+        if self.offset >= self.source.len() {
             return Err(Error::UnexpectedEof.into());
         }
         match unsafe { self.source.get_u8_char(self.offset) } {
@@ -39,7 +61,7 @@ impl<'de, S: Str<'de>> Source<'de, S> for Cursor<S> {
 
     fn read_str(&mut self, n: usize) -> IoResult<S> {
         let j = self.offset + n;
-        if j >= self.source.len() {
+        if j > self.source.len() {
             return Err(Error::UnexpectedEof.into());
         }
         match unsafe { self.source.clone_slice(self.offset, j) } {
@@ -52,188 +74,571 @@ impl<'de, S: Str<'de>> Source<'de, S> for Cursor<S> {
     }
 
     unsafe fn read_until(&mut self, byte: u8) -> IoResult<S> {
-        let offset = match self.source.find(self.offset, byte) {
-            Some(offset) => offset,
-            None => return Err(Error::UnexpectedEof.into()),
+        if self.offset >= self.source.len() {
+            return Err(Error::UnexpectedEof.into());
+        }
+        let offset = if self.source.as_bytes().get(self.offset) == Some(&byte) {
+            self.offset
+        } else {
+            match self.source.find(self.offset, byte) {
+                Some(offset) => offset,
+                None => return Err(Error::UnexpectedEof.into()),
+            }
         };
-        self.read_str(offset - self.offset)
+        let ret = self.read_str(offset - self.offset)?;
+        self.offset += 1; // consume `byte`, which is ASCII
+        Ok(ret)
+    }
+}
+
+/// Limits on the resources used and the classes accepted when parsing untrusted input.
+///
+/// Each numeric limit is inclusive, and `usize::MAX` disables it.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// The maximum nesting depth of arrays and objects,
+    /// corresponding to the `unserialize_max_depth` ini setting of PHP.
+    ///
+    /// A top-level array has a depth of 1.
+    /// Defaults to 4096, the same as PHP.
+    /// The serde `Deserializer` and dropping a `Value` recurse once per level,
+    /// so unoptimized builds may need a larger stack than the default thread stack
+    /// to reach this depth.
+    pub max_depth: usize,
+    /// The maximum total number of array entries and object properties in the document.
+    ///
+    /// Defaults to unlimited.
+    pub max_elements: usize,
+    /// The maximum length in bytes of a single string,
+    /// including class names and `Serializable` payloads.
+    ///
+    /// Defaults to unlimited.
+    pub max_string_len: usize,
+    /// The maximum cumulative number of bytes allocated for strings and array/object entries.
+    ///
+    /// Strings are counted even if they are borrowed from the input.
+    /// Defaults to unlimited.
+    pub max_allocation: usize,
+    /// The classes allowed in `O:` and `C:` values,
+    /// corresponding to the `allowed_classes` option of PHP's `unserialize`.
+    ///
+    /// Defaults to allowing all classes.
+    pub allowed_classes: ClassPolicy,
+    /// What to do with `O:` and `C:` values of classes not allowed by `allowed_classes`.
+    ///
+    /// Defaults to downgrading them to incomplete objects, the same as PHP.
+    pub disallowed_classes: DisallowedClassAction,
+}
+
+/// The classes allowed by `ParseOptions::allowed_classes`.
+#[derive(Clone)]
+pub enum ClassPolicy {
+    /// All classes are allowed, like `'allowed_classes' => true`.
+    All,
+    /// No classes are allowed, like `'allowed_classes' => false`.
+    None,
+    /// Only the listed classes are allowed, like `'allowed_classes' => [...]`.
+    ///
+    /// Class names are compared ASCII-case-insensitively, the same as PHP.
+    List(Vec<String>),
+    /// Only the classes for which the function returns true are allowed.
+    #[allow(clippy::type_complexity)]
+    Predicate(Arc<dyn Fn(&[u8]) -> bool + Send + Sync>),
+}
+
+impl ClassPolicy {
+    /// Creates a `Predicate` policy from a function or closure.
+    pub fn predicate(predicate: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        Self::Predicate(Arc::new(predicate))
+    }
+
+    /// Returns whether `class` is allowed by this policy.
+    pub fn allows(&self, class: &[u8]) -> bool {
+        match self {
+            Self::All => true,
+            Self::None => false,
+            Self::List(list) => list
+                .iter()
+                .any(|allowed| allowed.as_bytes().eq_ignore_ascii_case(class)),
+            Self::Predicate(predicate) => predicate(class),
+        }
+    }
+}
+
+impl fmt::Debug for ClassPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => f.write_str("All"),
+            Self::None => f.write_str("None"),
+            Self::List(list) => f.debug_tuple("List").field(list).finish(),
+            Self::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+/// The handling of classes not allowed by `ParseOptions::allowed_classes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisallowedClassAction {
+    /// Fails with `Error::DisallowedClass`.
+    Reject,
+    /// Replaces the object with an `__PHP_Incomplete_Class` object, the same as PHP.
+    ///
+    /// The original class name is stored in the `__PHP_Incomplete_Class_Name` property,
+    /// followed by the original properties.
+    /// Like PHP, the payload of a `Serializable` object is discarded,
+    /// so emitting the value again produces an `O:` object without the payload.
+    ///
+    /// The serde `Deserializer` visits such objects as maps
+    /// with the original class in a leading `__PHP_Incomplete_Class_Name` entry.
+    Incomplete,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 4096,
+            max_elements: usize::MAX,
+            max_string_len: usize::MAX,
+            max_allocation: usize::MAX,
+            allowed_classes: ClassPolicy::All,
+            disallowed_classes: DisallowedClassAction::Incomplete,
+        }
+    }
+}
+
+/// Tracks the resources used so far against `ParseOptions`.
+pub(crate) struct Limits {
+    options: ParseOptions,
+    usage: Usage,
+}
+
+/// The resources used so far, which can be saved and restored to retry parsing.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Usage {
+    depth: usize,
+    elements: usize,
+    allocated: usize,
+}
+
+impl Limits {
+    pub(crate) fn new(options: ParseOptions) -> Self {
+        Self {
+            options,
+            usage: Usage::default(),
+        }
+    }
+
+    pub(crate) fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    pub(crate) fn restore(&mut self, usage: Usage) {
+        self.usage = usage;
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.usage = Usage::default();
+    }
+
+    /// Enters an array or object with `len` entries.
+    pub(crate) fn enter(&mut self, len: usize, offset: usize) -> Result {
+        self.usage.depth += 1;
+        if self.usage.depth > self.options.max_depth {
+            return Err(Error::DepthLimitExceeded(offset));
+        }
+        self.usage.elements = self.usage.elements.saturating_add(len);
+        if self.usage.elements > self.options.max_elements {
+            return Err(Error::ElementLimitExceeded(offset));
+        }
+        Ok(())
+    }
+
+    /// Leaves the innermost array or object.
+    pub(crate) fn leave(&mut self) {
+        self.usage.depth -= 1;
+    }
+
+    /// Reserves a string of `len` bytes.
+    pub(crate) fn string(&mut self, len: usize, offset: usize) -> Result {
+        if len > self.options.max_string_len {
+            return Err(Error::StringLimitExceeded(offset));
+        }
+        self.allocate(len, offset)
+    }
+
+    /// Checks `class` against the class policy.
+    ///
+    /// Returns `Ok(false)` if the value should be downgraded to an incomplete object.
+    /// An object that is already incomplete is kept as is rather than downgraded again.
+    pub(crate) fn check_class(&self, class: &[u8], offset: usize) -> Result<bool> {
+        if self.options.allowed_classes.allows(class) {
+            return Ok(true);
+        }
+        match self.options.disallowed_classes {
+            DisallowedClassAction::Reject => Err(Error::DisallowedClass(offset)),
+            DisallowedClassAction::Incomplete => {
+                Ok(class.eq_ignore_ascii_case(INCOMPLETE_CLASS.as_bytes()))
+            }
+        }
+    }
+
+    pub(crate) fn allocate(&mut self, bytes: usize, offset: usize) -> Result {
+        self.usage.allocated = self.usage.allocated.saturating_add(bytes);
+        if self.usage.allocated > self.options.max_allocation {
+            return Err(Error::AllocationLimitExceeded(offset));
+        }
+        Ok(())
     }
 }
 
 impl<'de, S: Str<'de>> Value<S> {
     /// Parses a string or byte array
+    ///
+    /// Any input after the value is ignored;
+    /// use `parse_exact` to reject it or `parse_prefix` to retrieve it.
     pub fn parse(source: S) -> IoResult<Self> {
+        Self::parse_with(source, &ParseOptions::default())
+    }
+
+    /// Parses a string or byte array with the specified limits
+    pub fn parse_with(source: S, options: &ParseOptions) -> IoResult<Self> {
         let cursor = Cursor { offset: 0, source };
-        Self::from_source(cursor)
+        Self::from_source_with(cursor, options)
     }
 
-    /// Parses a stream
-    pub fn from_source(mut source: impl Source<'de, S>) -> IoResult<Self> {
-        match source.read_u8_char()? {
-            b'N' => read_null(source),
-            b'b' => read_bool(source),
-            b'i' => read_int(source),
-            b'd' => read_float(source),
-            b's' => read_string(source),
-            b'a' => read_array(source),
-            b'O' => read_object(source),
-            b'C' => read_ser(source),
-            b'R' => read_ref(source),
-            _ => Err(Error::BadToken(source.offset()).into()),
+    /// Parses a value at the start of a string or byte array,
+    /// returning the value and the input after it.
+    ///
+    /// This is useful for reading concatenated values
+    /// or detecting how many bytes a value occupies.
+    pub fn parse_prefix(source: S) -> IoResult<(Self, S)> {
+        Self::parse_prefix_with(source, &ParseOptions::default())
+    }
+
+    /// Parses a value at the start of a string or byte array with the specified limits,
+    /// returning the value and the input after it.
+    pub fn parse_prefix_with(source: S, options: &ParseOptions) -> IoResult<(Self, S)> {
+        let mut cursor = Cursor::new(source);
+        let value = read_value(&mut cursor, &mut Limits::new(options.clone()))?;
+        Ok((value, cursor.remaining()))
+    }
+
+    /// Parses a string or byte array that must contain exactly one value.
+    ///
+    /// # Errors
+    /// Returns `Error::TrailingData` if there is input after the value.
+    pub fn parse_exact(source: S) -> IoResult<Self> {
+        Self::parse_exact_with(source, &ParseOptions::default())
+    }
+
+    /// Parses a string or byte array that must contain exactly one value
+    /// with the specified limits.
+    ///
+    /// # Errors
+    /// Returns `Error::TrailingData` if there is input after the value.
+    pub fn parse_exact_with(source: S, options: &ParseOptions) -> IoResult<Self> {
+        let mut cursor = Cursor::new(source);
+        let value = read_value(&mut cursor, &mut Limits::new(options.clone()))?;
+        if cursor.offset < cursor.source.len() {
+            return Err(Error::TrailingData(cursor.offset).into());
         }
+        Ok(value)
+    }
+
+    /// Parses a stream
+    pub fn from_source(source: impl Source<'de, S>) -> IoResult<Self> {
+        Self::from_source_with(source, &ParseOptions::default())
     }
+
+    /// Parses a stream with the specified limits
+    pub fn from_source_with(
+        mut source: impl Source<'de, S>,
+        options: &ParseOptions,
+    ) -> IoResult<Self> {
+        read_value(&mut source, &mut Limits::new(options.clone()))
+    }
+}
+
+pub(crate) fn read_value<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<Value<S>> {
+    build_value(&mut EventState::new(), source, limits)
 }
 
-fn read_null<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b';')?;
-    Ok(Value::Null)
+/// Reads the rest of a value after its tag byte `tag` has been consumed.
+pub(crate) fn read_value_tagged<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    tag: u8,
+    limits: &mut Limits,
+) -> IoResult<Value<S>> {
+    build_value(&mut EventState::with_tag(tag), source, limits)
 }
 
-fn read_bool<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
+/// Skips the rest of a value after its tag byte `tag` has been consumed,
+/// reading it as events without building a `Value`.
+#[cfg(feature = "serde")]
+pub(crate) fn skip_value_tagged<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    tag: u8,
+    limits: &mut Limits,
+) -> IoResult {
+    let mut state = EventState::with_tag(tag);
+    while state.next_event(source, limits)?.is_some() {}
+    Ok(())
+}
+
+pub(crate) fn read_null<'de, S: Str<'de>, Src: Source<'de, S>>(source: &mut Src) -> IoResult {
+    expect_char(source, b';')
+}
+
+pub(crate) fn read_bool<'de, S: Str<'de>, Src: Source<'de, S>>(source: &mut Src) -> IoResult<bool> {
+    expect_char(source, b':')?;
     let bool = match source.read_u8_char()? {
         b'1' => true,
         b'0' => false,
         _ => return Err(Error::BadNumber(source.offset()).into()),
     };
-    expect_char(&mut source, b';')?;
-    Ok(Value::Bool(bool))
+    expect_char(source, b';')?;
+    Ok(bool)
 }
 
-fn read_int<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
-    Ok(Value::Int(parse_before::<'_, i64, _, _>(
-        &mut source,
-        b';',
-    )?))
+pub(crate) fn read_int<'de, S: Str<'de>, Src: Source<'de, S>>(source: &mut Src) -> IoResult<i64> {
+    expect_char(source, b':')?;
+    parse_before::<i64, _, _>(source, b';')
 }
 
-fn read_float<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
-    Ok(Value::Float(parse_before::<'_, f64, _, _>(
-        &mut source,
-        b';',
-    )?))
+pub(crate) fn read_float<'de, S: Str<'de>, Src: Source<'de, S>>(source: &mut Src) -> IoResult<f64> {
+    expect_char(source, b':')?;
+    let bytes = unsafe { source.read_until(b';') }?;
+    let float = parse_float(bytes.as_bytes()).ok_or_else(|| Error::BadNumber(source.offset()))?;
+    Ok(float)
+}
+
+/// Parses a float in the syntax accepted by PHP's `unserialize`.
+///
+/// This includes `NAN`, `INF`, `-INF`,
+/// integers, decimals with digits on either side of the point,
+/// and either of them followed by an exponent like `E+25`.
+fn parse_float(bytes: &[u8]) -> Option<f64> {
+    match bytes {
+        b"NAN" => return Some(f64::NAN),
+        b"INF" => return Some(f64::INFINITY),
+        b"-INF" => return Some(f64::NEG_INFINITY),
+        _ => {}
+    }
+
+    let rest = skip_sign(bytes);
+    let (int_digits, mut rest) = skip_digits(rest);
+    let mut frac_digits = 0;
+    if let Some((b'.', tail)) = rest.split_first() {
+        let (digits, tail) = skip_digits(tail);
+        frac_digits = digits;
+        rest = tail;
+    }
+    if int_digits == 0 && frac_digits == 0 {
+        return None;
+    }
+    if let Some((b'e', tail)) | Some((b'E', tail)) = rest.split_first() {
+        let (exp_digits, tail) = skip_digits(skip_sign(tail));
+        if exp_digits == 0 {
+            return None;
+        }
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+
+    str::from_utf8(bytes).ok()?.parse().ok()
 }
 
-fn read_string<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
-    let len = parse_before::<'_, usize, _, _>(&mut source, b':')?;
-    expect_char(&mut source, b'"')?;
+fn skip_sign(bytes: &[u8]) -> &[u8] {
+    match bytes.split_first() {
+        Some((b'+', tail)) | Some((b'-', tail)) => tail,
+        _ => bytes,
+    }
+}
+
+fn skip_digits(bytes: &[u8]) -> (usize, &[u8]) {
+    let count = bytes
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    (count, bytes.split_at(count).1)
+}
+
+pub(crate) fn read_string<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<S> {
+    expect_char(source, b':')?;
+    let len = parse_before::<usize, _, _>(source, b':')?;
+    limits.string(len, source.offset())?;
+    expect_char(source, b'"')?;
     let content = source.read_str(len)?;
-    expect_char(&mut source, b'"')?;
-    expect_char(&mut source, b';')?;
-    Ok(Value::String(content))
+    expect_char(source, b'"')?;
+    expect_char(source, b';')?;
+    Ok(content)
 }
 
-fn read_array<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b';')?;
-    let len = parse_before::<'_, usize, _, _>(&mut source, b':')?;
-    expect_char(&mut source, b'{')?;
-    let mut vec = Vec::with_capacity(len);
+/// Reads the `:len:{` part of an array and returns the number of entries.
+///
+/// The caller must call `Limits::leave` after reading the closing `}`.
+pub(crate) fn read_array_header<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<usize> {
+    expect_char(source, b':')?;
+    let len = parse_before::<usize, _, _>(source, b':')?;
     if len > source.limit() {
         return Err(Error::UnexpectedEof.into());
     }
-    for _ in 0..len {
-        let key = match Value::from_source(&mut source)? {
-            Value::Int(int) => ArrayKey::Int(int),
-            Value::String(string) => ArrayKey::String(string),
-            _ => return Err(Error::BadArrayKeyType(source.offset()).into()),
-        };
-        let value = Value::from_source(&mut source)?;
-        vec.push((key, value));
+    limits.enter(len, source.offset())?;
+    expect_char(source, b'{')?;
+    Ok(len)
+}
+
+pub(crate) fn read_array_key<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<ArrayKey<S>> {
+    match source.read_u8_char()? {
+        b'i' => Ok(ArrayKey::Int(read_int(source)?)),
+        b's' => Ok(ArrayKey::String(read_string(source, limits)?)),
+        _ => Err(Error::BadArrayKeyType(source.offset()).into()),
     }
-    expect_char(&mut source, b'}')?;
-    Ok(Value::Array(vec))
 }
 
-fn read_object<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
-    let len = parse_before::<'_, usize, _, _>(&mut source, b':')?;
-    expect_char(&mut source, b'"')?;
-    let class = source.read_str(len)?;
-    expect_char(&mut source, b'"')?;
-    expect_char(&mut source, b':')?;
-    let properties_len = parse_before::<'_, usize, _, _>(&mut source, b':')?;
+/// Reads the `:len:"class":n:{` part of an object and returns the class and number of properties.
+///
+/// The caller must call `Limits::leave` after reading the closing `}`.
+pub(crate) fn read_object_header<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<(S, usize)> {
+    let class = read_class(source, limits)?;
+    let properties_len = parse_before::<usize, _, _>(source, b':')?;
     if properties_len > source.limit() {
         return Err(Error::UnexpectedEof.into());
     }
-    expect_char(&mut source, b'{')?;
+    limits.enter(properties_len, source.offset())?;
+    expect_char(source, b'{')?;
+    Ok((class, properties_len))
+}
 
-    let mut properties = Vec::with_capacity(properties_len);
-    for _ in 0..properties_len {
-        let name = match Value::from_source(&mut source)? {
-            Value::String(string) => string,
-            _ => return Err(Error::BadObjectKeyType(source.offset()).into()),
-        };
+/// Reads the `:len:"class":` part shared by `O:` and `C:` values.
+fn read_class<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<S> {
+    expect_char(source, b':')?;
+    let len = parse_before::<usize, _, _>(source, b':')?;
+    limits.string(len, source.offset())?;
+    expect_char(source, b'"')?;
+    let class = source.read_str(len)?;
+    expect_char(source, b'"')?;
+    expect_char(source, b':')?;
+    Ok(class)
+}
+
+/// Reads a property name and undoes the visibility mangling.
+pub(crate) fn read_property_name<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<PropertyName<S>> {
+    let name = match source.read_u8_char()? {
+        b's' => read_string(source, limits)?,
+        _ => return Err(Error::BadObjectKeyType(source.offset()).into()),
+    };
+    Ok(unmangle_property_name(name, source.offset())?)
+}
 
-        let name_bytes = name.as_bytes();
-        let (name, vis) = if name_bytes.get(0) == Some(&0) {
-            if name_bytes.get(1) == Some(&b'*') {
-                if name_bytes.get(2) != Some(&0) {
-                    return Err(Error::BadToken(source.offset()).into());
-                }
-                // encoding and length checked above
-                (unsafe { name.range_from(3) }, PropertyVis::Protected)
-            } else {
-                let second_null = name_bytes
-                    .iter()
-                    .skip(1)
-                    .position(|&b| b == 0)
-                    .ok_or_else(|| Error::UnexpectedEof)?
-                    + 1; // +1 because skip(1)
-                let priv_class = unsafe { name.range(1, second_null) };
-                (
-                    unsafe { name.range_from(second_null + 1) },
-                    PropertyVis::Private(priv_class),
-                )
+/// Undoes the visibility mangling of a property name,
+/// reporting malformed names at `offset`.
+pub(crate) fn unmangle_property_name<'de, S: Str<'de>>(
+    name: S,
+    offset: usize,
+) -> Result<PropertyName<S>> {
+    let name_bytes = name.as_bytes();
+    let (name, vis) = if name_bytes.first() == Some(&0) {
+        if name_bytes.get(1) == Some(&b'*') {
+            if name_bytes.get(2) != Some(&0) {
+                return Err(Error::BadToken(offset));
             }
+            // encoding and length checked above
+            (unsafe { name.range_from(3) }, PropertyVis::Protected)
         } else {
-            (name, PropertyVis::Public)
-        };
-
-        let value = Value::from_source(&mut source)?;
-        properties.push((PropertyName::new(vis, name), value));
-    }
-
-    expect_char(&mut source, b'}')?;
-
-    Ok(Value::Object(Object::new(class, properties)))
+            let second_null = name_bytes
+                .iter()
+                .skip(1)
+                .position(|&b| b == 0)
+                .ok_or(Error::BadToken(offset))?
+                + 1; // +1 because skip(1)
+            let priv_class = unsafe { name.range(1, second_null) };
+            (
+                unsafe { name.range_from(second_null + 1) },
+                PropertyVis::Private(priv_class),
+            )
+        }
+    } else {
+        (name, PropertyVis::Public)
+    };
+    Ok(PropertyName::new(vis, name))
 }
 
-fn read_ser<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
-    let class_len = parse_before::<'_, usize, _, _>(&mut source, b':')?;
-    expect_char(&mut source, b'"')?;
-    let class = source.read_str(class_len)?;
-    expect_char(&mut source, b'"')?;
-    expect_char(&mut source, b':')?;
-
-    let data_len = parse_before::<'_, usize, _, _>(&mut source, b':')?;
-    expect_char(&mut source, b'{')?;
+pub(crate) fn read_ser<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<Serializable<S>> {
+    let class = read_class(source, limits)?;
+    let data_len = parse_before::<usize, _, _>(source, b':')?;
+    limits.string(data_len, source.offset())?;
+    expect_char(source, b'{')?;
     let data = source.read_str(data_len)?;
-    expect_char(&mut source, b'}')?;
+    expect_char(source, b'}')?;
 
-    Ok(Value::Serializable(Serializable::new(class, data)))
+    Ok(Serializable::new(class, data))
 }
 
-fn read_ref<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> IoResult<Value<S>> {
-    expect_char(&mut source, b':')?;
-    let index = parse_before::<'_, usize, _, _>(&mut source, b';')?;
+pub(crate) fn read_enum<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    limits: &mut Limits,
+) -> IoResult<EnumCase<S>> {
+    let name = read_string(source, limits)?;
+    let colon = name
+        .as_bytes()
+        .iter()
+        .position(|&b| b == b':')
+        .ok_or_else(|| Error::BadEnumName(source.offset()))?;
+    if colon == 0 || colon + 1 == name.len() {
+        return Err(Error::BadEnumName(source.offset()).into());
+    }
+    // `:` is ASCII, so both sides are boundaries
+    let class = unsafe { name.range(0, colon) };
+    let case = unsafe { name.range_from(colon + 1) };
+    Ok(EnumCase::new(class, case))
+}
 
-    Ok(Value::Reference(Ref::new(index)))
+pub(crate) fn read_ref<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    kind: RefKind,
+) -> IoResult<Ref> {
+    expect_char(source, b':')?;
+    let index = parse_before::<usize, _, _>(source, b';')?;
+
+    Ok(Ref::with_kind(kind, index))
 }
 
-fn expect_char<'de, S: Str<'de>>(mut source: impl Source<'de, S>, char: u8) -> IoResult {
-    if source.read_u8_char()? == char {
-        Ok(())
-    } else {
-        Err(Error::BadToken(source.offset()).into())
-    }
+pub(crate) fn expect_char<'de, S: Str<'de>, Src: Source<'de, S>>(
+    source: &mut Src,
+    char: u8,
+) -> IoResult {
+    source.expect_u8_char(char)
 }
 
 fn parse_before<'de, T: str::FromStr, S: Str<'de>, Src: Source<'de, S>>(
-    mut source: Src,
+    source: &mut Src,
     char: u8,
 ) -> IoResult<T> {
     let bytes = unsafe { source.read_until(char) }?;
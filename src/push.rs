@@ -0,0 +1,269 @@
+use std::marker::PhantomData;
+
+use crate::event::{EventState, ValueBuilder};
+use crate::parse::Limits;
+use crate::*;
+
+/// A resumable parser that is fed the input in chunks,
+/// for use where blocking on `io::Read` is not possible.
+///
+/// The parser buffers the bytes of an incomplete token
+/// and reports `Progress::NeedMoreData` until the rest of it is fed,
+/// so chunks may be split at any byte.
+///
+/// Like `EventParser`, the parser reads one value,
+/// which can be consumed as events or as a `Value`.
+/// Strings in the value are copied out of the buffer as `S`,
+/// which is either `Vec<u8>` or `String`.
+///
+/// Array and object lengths are checked against the `limit` given to `with_limit`,
+/// and storage for their entries is only allocated as the entries arrive,
+/// so a huge declared length cannot exhaust memory.
+pub struct PushParser<S = Vec<u8>> {
+    /// The bytes that have been fed but not consumed, starting at `buffer[consumed]`
+    buffer: Vec<u8>,
+    consumed: usize,
+    /// The offset of `buffer[0]` in the whole input
+    base: usize,
+    /// The maximum possible number of bytes in the whole input
+    limit: usize,
+    state: EventState,
+    limits: Limits,
+    builder: Option<ValueBuilder<S>>,
+}
+
+/// An owned string type that can be copied out of a byte buffer.
+pub trait OwnedStr: for<'de> Str<'de> {
+    /// Returns whether `byte` may be read as a single character.
+    fn is_char(byte: u8) -> bool;
+
+    /// Copies `bytes` into a new string,
+    /// or returns `None` if they are not a valid string of this type.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+impl OwnedStr for Vec<u8> {
+    fn is_char(_: u8) -> bool {
+        true
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl OwnedStr for String {
+    fn is_char(byte: u8) -> bool {
+        byte.is_ascii()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+/// The result of a `PushParser` step.
+#[derive(Debug, Clone)]
+pub enum Progress<T> {
+    /// The step completed with a result.
+    Ready(T),
+    /// The fed input ends in the middle of a token;
+    /// feed more bytes and try again.
+    NeedMoreData,
+    /// The value has been completely parsed.
+    Finished,
+}
+
+impl<S: OwnedStr> Default for PushParser<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: OwnedStr> PushParser<S> {
+    /// Creates a parser with no input.
+    pub fn new() -> Self {
+        Self::with_options(&ParseOptions::default())
+    }
+
+    /// Creates a parser with no input and the specified limits.
+    pub fn with_options(options: &ParseOptions) -> Self {
+        Self::with_limit(usize::MAX, options)
+    }
+
+    /// Creates a parser with no input that will be fed at most `limit` bytes in total.
+    ///
+    /// Like the `limit` of `ByteReader`,
+    /// this rejects array and object lengths that cannot fit in the input.
+    pub fn with_limit(limit: usize, options: &ParseOptions) -> Self {
+        Self {
+            buffer: vec![],
+            consumed: 0,
+            base: 0,
+            limit,
+            state: EventState::new(),
+            limits: Limits::new(options.clone()),
+            builder: None,
+        }
+    }
+
+    /// Appends a chunk to the input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if self.consumed > 0 {
+            let _ = self.buffer.drain(..self.consumed);
+            self.base += self.consumed;
+            self.consumed = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Returns the number of bytes consumed from the whole input.
+    pub fn offset(&self) -> usize {
+        self.base + self.consumed
+    }
+
+    /// Returns the bytes that have been fed but not consumed.
+    ///
+    /// After the value is finished, this is the input after the value.
+    pub fn remaining(&self) -> &[u8] {
+        self.buffer.get(self.consumed..).unwrap_or_default()
+    }
+
+    /// Returns whether the value has been completely parsed.
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+
+    /// Prepares the parser to read another value after the current one,
+    /// such as the next of several concatenated values.
+    ///
+    /// The unconsumed input is kept, offsets continue from the current value,
+    /// and the limits are applied to the next value afresh.
+    pub fn restart(&mut self) {
+        self.state = EventState::new();
+        self.limits.reset();
+        self.builder = None;
+    }
+
+    /// Reads the next event from the fed input.
+    ///
+    /// Event offsets are relative to the start of the whole input.
+    ///
+    /// # Errors
+    /// Returns any syntax error or exceeded limit in the input.
+    /// `Error::UnexpectedEof` is only returned
+    /// if more data is needed after all of the `limit` bytes have been fed;
+    /// otherwise, call `is_finished` after the input ends to detect truncation.
+    pub fn next_event(&mut self) -> Result<Progress<Event<S>>> {
+        let mut reader = SliceReader {
+            buffer: &self.buffer,
+            offset: self.consumed,
+            limit: self.limit.saturating_sub(self.base),
+            _ph: PhantomData,
+        };
+        let usage = self.limits.usage();
+        match self.state.next_event(&mut reader, &mut self.limits) {
+            Ok(Some(event)) => {
+                self.consumed = reader.offset;
+                let (offset, kind) = (event.offset(), event.into_kind());
+                Ok(Progress::Ready(Event::new(offset + self.base, kind)))
+            }
+            Ok(None) => Ok(Progress::Finished),
+            Err(IoError::Phpser(Error::UnexpectedEof)) => {
+                if self.base + self.buffer.len() >= self.limit {
+                    return Err(Error::UnexpectedEof);
+                }
+                self.limits.restore(usage);
+                Ok(Progress::NeedMoreData)
+            }
+            Err(IoError::Phpser(err)) => Err(err.offset_by(self.base)),
+            Err(IoError::Io(_)) => unreachable!("SliceReader does not perform IO"),
+        }
+    }
+
+    /// Reads the next value from the fed input.
+    ///
+    /// The value may span any number of calls that return `Progress::NeedMoreData`.
+    ///
+    /// # Panics
+    /// Panics if this is called for a new value where the next event would not start a value,
+    /// i.e. unless no events have been read yet
+    /// or the last event read with `next_event` was `Key` or `PropertyName`.
+    ///
+    /// # Errors
+    /// See `next_event`.
+    pub fn read_value(&mut self) -> Result<Progress<Value<S>>> {
+        if self.builder.is_none() {
+            if self.state.is_finished() {
+                return Ok(Progress::Finished);
+            }
+            assert!(
+                self.state.expects_value(),
+                "PushParser::read_value called where no value is expected"
+            );
+            self.builder = Some(ValueBuilder::new());
+        }
+        loop {
+            let event = match self.next_event()? {
+                Progress::Ready(event) => event,
+                Progress::NeedMoreData => return Ok(Progress::NeedMoreData),
+                Progress::Finished => return Err(Error::UnexpectedEof),
+            };
+            let builder = self.builder.as_mut().expect("builder was created above");
+            if let Some(value) = builder.push(event, &mut self.limits)? {
+                self.builder = None;
+                return Ok(Progress::Ready(value));
+            }
+        }
+    }
+}
+
+/// A `Source` over the buffer of a `PushParser`.
+struct SliceReader<'b, S> {
+    buffer: &'b [u8],
+    offset: usize,
+    /// The maximum possible number of bytes from `buffer[0]`
+    limit: usize,
+    _ph: PhantomData<S>,
+}
+
+impl<'b, 'de, S: OwnedStr> Source<'de, S> for SliceReader<'b, S> {
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn limit(&self) -> usize {
+        self.limit
+    }
+
+    fn read_u8_char(&mut self) -> IoResult<u8> {
+        let byte = *self.buffer.get(self.offset).ok_or(Error::UnexpectedEof)?;
+        if !S::is_char(byte) {
+            return Err(Error::BadEncoding(self.offset).into());
+        }
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn read_str(&mut self, n: usize) -> IoResult<S> {
+        let end = self.offset.checked_add(n).ok_or(Error::UnexpectedEof)?;
+        let bytes = self
+            .buffer
+            .get(self.offset..end)
+            .ok_or(Error::UnexpectedEof)?;
+        let string = S::from_bytes(bytes).ok_or(Error::BadEncoding(self.offset))?;
+        self.offset = end;
+        Ok(string)
+    }
+
+    unsafe fn read_until(&mut self, byte: u8) -> IoResult<S> {
+        let rest = self.buffer.get(self.offset..).unwrap_or_default();
+        let len = rest
+            .iter()
+            .position(|&other| other == byte)
+            .ok_or(Error::UnexpectedEof)?;
+        let ret = self.read_str(len)?;
+        self.offset += 1; // consume `byte`
+        Ok(ret)
+    }
+}
@@ -0,0 +1,703 @@
+use std::convert::TryFrom;
+use std::io::Write;
+
+use serde::ser::{self, Serialize};
+
+use crate::emit::{write_bool, write_class, write_enum_case, write_float, write_int, write_string};
+use crate::types::parse_int_key;
+use crate::*;
+
+/// Serializes `value` into a string.
+///
+/// Returns an error if the output contains non-UTF-8 strings.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, SerdeError> {
+    String::from_utf8(to_vec(value)?)
+        .map_err(|_| SerdeError::Custom("serialized output is not valid UTF-8".into()))
+}
+
+/// Serializes `value` into a byte vector.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerdeError> {
+    let mut ser = Serializer::new(SerializerOptions::default());
+    value.serialize(&mut ser)?;
+    Ok(ser.into_inner())
+}
+
+/// Serializes `value` into `write`.
+///
+/// The output is built in memory and written at once,
+/// because arrays of unknown length are only complete after their last entry.
+pub fn to_writer<T: Serialize + ?Sized>(
+    mut write: impl Write,
+    value: &T,
+) -> Result<(), SerdeError> {
+    let bytes = to_vec(value)?;
+    write.write_all(&bytes).map_err(SerdeError::Io)
+}
+
+/// Options for the serde `Serializer`.
+#[derive(Debug, Clone, Default)]
+pub struct SerializerOptions {
+    /// The representation of structs and struct variants.
+    pub structs: StructStyle,
+    /// The representation of unit enum variants.
+    pub unit_variants: UnitVariantStyle,
+    /// Options for emitting scalars.
+    pub emit: EmitOptions,
+}
+
+/// The representation of structs.
+#[derive(Debug, Clone, Default)]
+pub enum StructStyle {
+    /// Associative arrays from field names to values.
+    #[default]
+    Array,
+    /// `O:` objects with public properties.
+    Object(ClassName),
+}
+
+/// The representation of unit enum variants.
+#[derive(Debug, Clone, Default)]
+pub enum UnitVariantStyle {
+    /// Strings containing the variant name.
+    #[default]
+    String,
+    /// PHP 8.1 `E:` enum cases named after the variant.
+    Enum(ClassName),
+}
+
+/// Determines the PHP class name for a Rust type.
+#[derive(Debug, Clone, Default)]
+pub enum ClassName {
+    /// Use the Rust type name as the class name.
+    #[default]
+    TypeName,
+    /// Prefix the Rust type name with a namespace, e.g. `App\Models\`.
+    Namespace(String),
+    /// Compute the class name from the Rust type name.
+    Custom(fn(&'static str) -> String),
+}
+
+impl ClassName {
+    fn resolve(&self, name: &'static str) -> String {
+        match self {
+            Self::TypeName => name.to_string(),
+            Self::Namespace(namespace) => format!("{}{}", namespace, name),
+            Self::Custom(f) => f(name),
+        }
+    }
+}
+
+/// A serde `Serializer` that writes PHP serialization format.
+///
+/// Sequences and tuples become arrays with int keys from 0,
+/// maps become arrays with int or string keys,
+/// `None` and unit values become `N;`,
+/// and non-unit enum variants become single-entry arrays
+/// from the variant name to the content.
+pub struct Serializer {
+    out: Vec<u8>,
+    options: SerializerOptions,
+}
+
+impl Serializer {
+    /// Creates a serializer with the specified options.
+    pub fn new(options: SerializerOptions) -> Self {
+        Self {
+            out: vec![],
+            options,
+        }
+    }
+
+    /// Consumes the serializer and returns the serialized bytes.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.out
+    }
+
+    fn compound(&mut self, header: Header, len: Option<usize>) -> Compound<'_> {
+        let start = self.out.len();
+        if let Some(len) = len {
+            header.write(&mut self.out, len);
+        }
+        Compound {
+            ser: self,
+            start,
+            count: 0,
+            header,
+            len,
+            variant: false,
+        }
+    }
+
+    /// Starts a compound value wrapped in a single-entry array keyed by the variant name.
+    fn variant_compound(
+        &mut self,
+        variant: &'static str,
+        header: Header,
+        len: usize,
+    ) -> Compound<'_> {
+        self.out.extend_from_slice(b"a:1:{");
+        write_string(&mut self.out, variant.as_bytes()).expect("Writing to a Vec<u8> never fails");
+        let mut compound = self.compound(header, Some(len));
+        compound.variant = true;
+        compound
+    }
+
+    fn struct_header(&self, name: &'static str) -> Header {
+        match &self.options.structs {
+            StructStyle::Array => Header::Array,
+            StructStyle::Object(class) => Header::Object(class.resolve(name)),
+        }
+    }
+}
+
+fn write_u64(out: &mut Vec<u8>, int: u64) -> Result<(), SerdeError> {
+    let int = i64::try_from(int)
+        .map_err(|_| SerdeError::Custom(format!("{} is out of range for PHP int", int)))?;
+    write_int(out, int).map_err(SerdeError::Io)
+}
+
+fn write_i128(out: &mut Vec<u8>, int: i128) -> Result<(), SerdeError> {
+    let int = i64::try_from(int)
+        .map_err(|_| SerdeError::Custom(format!("{} is out of range for PHP int", int)))?;
+    write_int(out, int).map_err(SerdeError::Io)
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = SerdeError;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> {
+        write_bool(&mut self.out, v).map_err(SerdeError::Io)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> {
+        write_int(&mut self.out, v).map_err(SerdeError::Io)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), SerdeError> {
+        write_i128(&mut self.out, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> {
+        write_u64(&mut self.out, v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), SerdeError> {
+        let v = u64::try_from(v)
+            .map_err(|_| SerdeError::Custom(format!("{} is out of range for PHP int", v)))?;
+        write_u64(&mut self.out, v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), SerdeError> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), SerdeError> {
+        write_float(&mut self.out, v, self.options.emit.float_precision).map_err(SerdeError::Io)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SerdeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        write_string(&mut self.out, v).map_err(SerdeError::Io)
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        self.out.extend_from_slice(b"N;");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerdeError> {
+        match &self.options.unit_variants {
+            UnitVariantStyle::String => self.serialize_str(variant),
+            UnitVariantStyle::Enum(class) => {
+                let class = class.resolve(name);
+                write_enum_case(&mut self.out, class.as_bytes(), variant.as_bytes())
+                    .map_err(SerdeError::Io)
+            }
+        }
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.out.extend_from_slice(b"a:1:{");
+        self.serialize_str(variant)?;
+        value.serialize(&mut *self)?;
+        self.out.push(b'}');
+        Ok(())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a>, SerdeError> {
+        Ok(self.compound(Header::Array, len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a>, SerdeError> {
+        Ok(self.compound(Header::Array, Some(len)))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, SerdeError> {
+        Ok(self.compound(Header::Array, Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, SerdeError> {
+        Ok(self.variant_compound(variant, Header::Array, len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a>, SerdeError> {
+        Ok(self.compound(Header::Array, len))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Compound<'a>, SerdeError> {
+        let header = self.struct_header(name);
+        Ok(self.compound(header, Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>, SerdeError> {
+        let header = self.struct_header(name);
+        Ok(self.variant_compound(variant, header, len))
+    }
+}
+
+/// The header of a compound value, written when the number of entries is known.
+enum Header {
+    Array,
+    Object(String),
+}
+
+impl Header {
+    fn write(&self, out: &mut Vec<u8>, len: usize) {
+        match self {
+            Header::Array => write!(out, "a:{}:{{", len),
+            Header::Object(class) => write_class(&mut *out, b'O', class.as_bytes())
+                .and_then(|()| write!(out, ":{}:{{", len)),
+        }
+        .expect("Writing to a Vec<u8> never fails");
+    }
+}
+
+/// Serializes the entries of an array or object.
+///
+/// If the length is known in advance, the header is written before the entries.
+/// Otherwise, the header is inserted at `start` after all entries are written.
+pub struct Compound<'a> {
+    ser: &'a mut Serializer,
+    start: usize,
+    count: usize,
+    header: Header,
+    /// The length that the header was written with, if known in advance
+    len: Option<usize>,
+    /// Whether the compound is wrapped in a single-entry variant array
+    variant: bool,
+}
+
+impl<'a> Compound<'a> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let index = i64::try_from(self.count).expect("Array length exceeds i64::MAX");
+        write_int(&mut self.ser.out, index).map_err(SerdeError::Io)?;
+        self.count += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.count += 1;
+        write_string(&mut self.ser.out, key.as_bytes()).map_err(SerdeError::Io)?;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<(), SerdeError> {
+        match self.len {
+            Some(len) if len != self.count => {
+                return Err(SerdeError::Custom(format!(
+                    "expected {} entries, but {} were serialized",
+                    len, self.count
+                )))
+            }
+            Some(_) => {}
+            None => {
+                let mut header = vec![];
+                self.header.write(&mut header, self.count);
+                let _ = self.ser.out.splice(self.start..self.start, header);
+            }
+        }
+        self.ser.out.push(b'}');
+        if self.variant {
+            self.ser.out.push(b'}');
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.count += 1;
+        key.serialize(KeySerializer {
+            out: &mut self.ser.out,
+        })
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+/// Serializes a map key as an array key.
+///
+/// Numeric string keys are converted to int keys like PHP does.
+struct KeySerializer<'a> {
+    out: &'a mut Vec<u8>,
+}
+
+impl<'a> KeySerializer<'a> {
+    fn bad_key() -> SerdeError {
+        SerdeError::Custom("array key must be int or string".into())
+    }
+}
+
+impl<'a> ser::Serializer for KeySerializer<'a> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    type SerializeSeq = ser::Impossible<(), SerdeError>;
+    type SerializeTuple = ser::Impossible<(), SerdeError>;
+    type SerializeTupleStruct = ser::Impossible<(), SerdeError>;
+    type SerializeTupleVariant = ser::Impossible<(), SerdeError>;
+    type SerializeMap = ser::Impossible<(), SerdeError>;
+    type SerializeStruct = ser::Impossible<(), SerdeError>;
+    type SerializeStructVariant = ser::Impossible<(), SerdeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), SerdeError> {
+        write_int(self.out, v).map_err(SerdeError::Io)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), SerdeError> {
+        write_i128(self.out, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), SerdeError> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), SerdeError> {
+        write_u64(self.out, v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), SerdeError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), SerdeError> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), SerdeError> {
+        match parse_int_key(v) {
+            Some(int) => write_int(self.out, int),
+            None => write_string(self.out, v),
+        }
+        .map_err(SerdeError::Io)
+    }
+
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(Self::bad_key())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(Self::bad_key())
+    }
+}
@@ -0,0 +1,273 @@
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use derive_new::new;
+use getset::Getters;
+
+use crate::emit::write_string;
+use crate::parse::expect_char;
+use crate::*;
+
+/// The delimiter between a variable name and its value in the `php` handler format.
+const PHP_DELIMITER: u8 = b'|';
+
+/// The bit of the name length that `php_binary` ignores,
+/// which older PHP versions used to mark undefined variables.
+const BINARY_UNDEF: u8 = 0x80;
+
+/// The maximum name length supported by the `php_binary` handler.
+const BINARY_MAX_NAME_LEN: usize = 0x7f;
+
+/// A value of the `session.serialize_handler` ini setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionHandler {
+    /// `php`, the default handler,
+    /// which stores `name|<serialized value>` for each variable without any separator.
+    Php,
+    /// `php_binary`,
+    /// which stores a length byte, the name and the serialized value for each variable.
+    PhpBinary,
+    /// `php_serialize`,
+    /// which stores the serialized `$_SESSION` array.
+    PhpSerialize,
+}
+
+impl SessionHandler {
+    /// Guesses which handler produced the session data `data`.
+    ///
+    /// Returns the first of `PhpSerialize`, `PhpBinary` and `Php`
+    /// that parses the whole data,
+    /// or `None` if the data is empty or no handler can parse it.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.is_empty() {
+            return None;
+        }
+        [Self::PhpSerialize, Self::PhpBinary, Self::Php]
+            .iter()
+            .copied()
+            .find(|&handler| {
+                let mut cursor = Cursor::new(data);
+                read_session(&mut cursor, handler, &ParseOptions::default()).is_ok()
+                    && cursor.remaining_bytes().is_empty()
+            })
+    }
+}
+
+/// The variables of a PHP session, in the order they are stored.
+///
+/// This corresponds to the contents of `$_SESSION`,
+/// which can be stored in the format of any `SessionHandler`.
+/// Methods without a `SessionHandler` parameter use `SessionHandler::Php`.
+///
+/// PHP numbers reference slots across all variables of a session,
+/// so an `r:` or `R:` in one value may refer to a value in a previous variable.
+/// References are kept as-is, so the session is written back byte-exactly,
+/// but `Value::resolve` cannot resolve references across variables.
+#[derive(Debug, Clone, Getters, new)]
+pub struct Session<S> {
+    /// The variable names and values.
+    #[getset(get = "pub")]
+    entries: Vec<(S, Value<S>)>,
+}
+
+impl<S> Session<S> {
+    /// Consumes the session and returns the variable names and values.
+    pub fn into_entries(self) -> Vec<(S, Value<S>)> {
+        self.entries
+    }
+}
+
+impl<'de, S: Str<'de>> Session<S> {
+    /// Parses session data in the `php` handler format.
+    ///
+    /// Like PHP, a variable name extends to the first `|`,
+    /// and trailing data without a `|` is ignored.
+    pub fn parse(source: S) -> IoResult<Self> {
+        Self::parse_with(source, &ParseOptions::default())
+    }
+
+    /// Parses session data in the `php` handler format with the specified limits.
+    ///
+    /// The limits apply to each value separately.
+    pub fn parse_with(source: S, options: &ParseOptions) -> IoResult<Self> {
+        Self::parse_as_with(source, SessionHandler::Php, options)
+    }
+
+    /// Parses session data in the format of `handler`.
+    ///
+    /// # Errors
+    /// For `SessionHandler::PhpSerialize`,
+    /// this returns `Error::BadArrayKeyType` if the array has an int key,
+    /// since it cannot be a variable of the other handlers.
+    pub fn parse_as(source: S, handler: SessionHandler) -> IoResult<Self> {
+        Self::parse_as_with(source, handler, &ParseOptions::default())
+    }
+
+    /// Parses session data in the format of `handler` with the specified limits.
+    ///
+    /// The limits apply to each value separately,
+    /// except for `SessionHandler::PhpSerialize`, which stores only one value.
+    pub fn parse_as_with(
+        source: S,
+        handler: SessionHandler,
+        options: &ParseOptions,
+    ) -> IoResult<Self> {
+        read_session(&mut Cursor::new(source), handler, options)
+    }
+
+    /// Serializes this session into a new byte vector in the `php` handler format.
+    ///
+    /// # Errors
+    /// Like PHP, this fails if a variable name contains `|`,
+    /// since it could not be parsed back.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        self.to_bytes_as(SessionHandler::Php)
+    }
+
+    /// Serializes this session into a new byte vector in the format of `handler`.
+    ///
+    /// # Errors
+    /// See `write_as_with`.
+    pub fn to_bytes_as(&self, handler: SessionHandler) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.write_as(&mut buf, handler)?;
+        Ok(buf)
+    }
+
+    /// Writes this session to `write` in the `php` handler format.
+    ///
+    /// Nothing is written if a variable name contains `|`.
+    ///
+    /// The output is not buffered;
+    /// consider wrapping `write` with an `io::BufWriter`.
+    pub fn write_to(&self, write: impl Write) -> io::Result<()> {
+        self.write_to_with(write, &EmitOptions::default())
+    }
+
+    /// Writes this session to `write` in the `php` handler format with the specified options.
+    pub fn write_to_with(&self, write: impl Write, options: &EmitOptions) -> io::Result<()> {
+        self.write_as_with(write, SessionHandler::Php, options)
+    }
+
+    /// Writes this session to `write` in the format of `handler`.
+    pub fn write_as(&self, write: impl Write, handler: SessionHandler) -> io::Result<()> {
+        self.write_as_with(write, handler, &EmitOptions::default())
+    }
+
+    /// Writes this session to `write` in the format of `handler` with the specified options.
+    ///
+    /// # Errors
+    /// Returns an `io::ErrorKind::InvalidInput` error without writing anything
+    /// if a variable name cannot be stored by `handler`:
+    /// - `SessionHandler::Php` fails if a name contains `|`, like PHP does.
+    /// - `SessionHandler::PhpBinary` fails if a name is longer than 127 bytes.
+    ///   PHP silently drops such variables instead.
+    pub fn write_as_with(
+        &self,
+        mut write: impl Write,
+        handler: SessionHandler,
+        options: &EmitOptions,
+    ) -> io::Result<()> {
+        let invalid = match handler {
+            SessionHandler::Php => self
+                .entries
+                .iter()
+                .any(|(name, _)| name.as_bytes().contains(&PHP_DELIMITER))
+                .then_some("session variable name contains `|`"),
+            SessionHandler::PhpBinary => self
+                .entries
+                .iter()
+                .any(|(name, _)| name.len() > BINARY_MAX_NAME_LEN)
+                .then_some("session variable name is longer than 127 bytes"),
+            SessionHandler::PhpSerialize => None,
+        };
+        if let Some(message) = invalid {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        match handler {
+            SessionHandler::Php => {
+                for (name, value) in &self.entries {
+                    write.write_all(name.as_bytes())?;
+                    write.write_all(&[PHP_DELIMITER])?;
+                    value.write_to_with(&mut write, options)?;
+                }
+            }
+            SessionHandler::PhpBinary => {
+                for (name, value) in &self.entries {
+                    let len = u8::try_from(name.len()).expect("name length checked above");
+                    write.write_all(&[len])?;
+                    write.write_all(name.as_bytes())?;
+                    value.write_to_with(&mut write, options)?;
+                }
+            }
+            SessionHandler::PhpSerialize => {
+                write!(write, "a:{}:{{", self.entries.len())?;
+                for (name, value) in &self.entries {
+                    write_string(&mut write, name.as_bytes())?;
+                    value.write_to_with(&mut write, options)?;
+                }
+                write.write_all(b"}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads session data in the format of `handler`,
+/// leaving `cursor` after the last variable.
+fn read_session<'de, S: Str<'de>>(
+    cursor: &mut Cursor<S>,
+    handler: SessionHandler,
+    options: &ParseOptions,
+) -> IoResult<Session<S>> {
+    let mut entries = vec![];
+    match handler {
+        SessionHandler::Php => {
+            // PHP stops at the first name without a `|` after it
+            while let Some(len) = cursor
+                .remaining_bytes()
+                .iter()
+                .position(|&byte| byte == PHP_DELIMITER)
+            {
+                let name = cursor.read_str(len)?;
+                expect_char(&mut *cursor, PHP_DELIMITER)?;
+                let value = Value::from_source_with(&mut *cursor, options)?;
+                entries.push((name, value));
+            }
+        }
+        SessionHandler::PhpBinary => {
+            while !cursor.remaining_bytes().is_empty() {
+                let len = usize::from(cursor.read_u8_char()? & !BINARY_UNDEF);
+                let name = cursor.read_str(len)?;
+                let value = Value::from_source_with(&mut *cursor, options)?;
+                entries.push((name, value));
+            }
+        }
+        SessionHandler::PhpSerialize => {
+            // PHP treats empty data as an empty session
+            if cursor.remaining_bytes().is_empty() {
+                return Ok(Session::new(entries));
+            }
+            let mut parser = EventParser::with_options(&mut *cursor, options);
+            let start = parser.next_event()?.ok_or(Error::UnexpectedEof)?;
+            if !matches!(start.kind(), EventKind::ArrayStart(_)) {
+                return Err(Error::BadToken(start.offset() + 1).into());
+            }
+            loop {
+                let event = parser.next_event()?.ok_or(Error::UnexpectedEof)?;
+                let offset = event.offset();
+                match event.into_kind() {
+                    EventKind::Key(ArrayKey::String(name)) => {
+                        entries.push((name, parser.read_value()?));
+                    }
+                    EventKind::Key(ArrayKey::Int(_)) => {
+                        return Err(Error::BadArrayKeyType(offset + 1).into());
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    Ok(Session::new(entries))
+}
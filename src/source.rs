@@ -34,6 +34,22 @@ pub trait Source<'de, S: Str<'de>> {
     /// the error is returned directly wrapped in `IoError::Io`.
     fn read_u8_char(&mut self) -> IoResult<u8>;
 
+    /// Reads one byte from the source and checks that it is `char`.
+    ///
+    /// Implementations should not override this method
+    /// unless they need to observe what the parser expects.
+    ///
+    /// # Errors
+    /// Returns `Error::BadToken` if the byte is not `char`,
+    /// in addition to the errors from `read_u8_char`.
+    fn expect_u8_char(&mut self, char: u8) -> IoResult {
+        if self.read_u8_char()? == char {
+            Ok(())
+        } else {
+            Err(Error::BadToken(self.offset()).into())
+        }
+    }
+
     /// Reads `n` *bytes* from the source.
     ///
     /// # Errors
@@ -58,7 +74,7 @@ pub trait Source<'de, S: Str<'de>> {
     unsafe fn read_until(&mut self, byte: u8) -> IoResult<S>;
 }
 
-impl<'t, 'de, S, T> Source<'de, S> for &'t mut T
+impl<'de, S, T> Source<'de, S> for &mut T
 where
     S: Str<'de>,
     T: Source<'de, S>,
@@ -75,6 +91,10 @@ where
         <T as Source<'de, S>>::read_u8_char(&mut **self)
     }
 
+    fn expect_u8_char(&mut self, char: u8) -> IoResult {
+        <T as Source<'de, S>>::expect_u8_char(&mut **self, char)
+    }
+
     fn read_str(&mut self, n: usize) -> IoResult<S> {
         <T as Source<'de, S>>::read_str(&mut **self, n)
     }
@@ -126,6 +146,7 @@ impl<'de, R: Read> Source<'de, Vec<u8>> for ByteReader<R> {
     fn read_u8_char(&mut self) -> IoResult<u8> {
         let mut buf = [0u8];
         self.read.read_exact(&mut buf)?;
+        self.offset += 1;
         Ok(buf[0])
     }
 
@@ -136,12 +157,13 @@ impl<'de, R: Read> Source<'de, Vec<u8>> for ByteReader<R> {
 
         let mut buf = vec![0u8; n];
         self.read.read_exact(&mut buf)?;
+        self.offset += n;
         Ok(buf)
     }
 
     unsafe fn read_until(&mut self, byte: u8) -> IoResult<Vec<u8>> {
-        let mut vec = vec![];
-        let _ = self.read.read_until(byte, &mut vec)?;
+        let vec = read_until_delimiter(&mut self.read, byte)?;
+        self.offset += vec.len() + 1;
         Ok(vec)
     }
 }
@@ -187,6 +209,7 @@ impl<'de, R: Read> Source<'de, String> for StringReader<R> {
         let mut buf = [0u8];
         self.read.read_exact(&mut buf)?;
         let _ = str::from_utf8(&buf).map_err(|_| Error::BadEncoding(self.offset))?;
+        self.offset += 1;
         Ok(buf[0])
     }
 
@@ -198,13 +221,26 @@ impl<'de, R: Read> Source<'de, String> for StringReader<R> {
         let mut buf = vec![0u8; n];
         self.read.read_exact(&mut buf)?;
         let string = String::from_utf8(buf).map_err(|_| Error::BadEncoding(self.offset))?;
+        self.offset += n;
         Ok(string)
     }
 
     unsafe fn read_until(&mut self, byte: u8) -> IoResult<String> {
-        let mut vec = vec![];
-        let _ = self.read.read_until(byte, &mut vec)?;
+        let vec = read_until_delimiter(&mut self.read, byte)?;
+        let len = vec.len();
         let string = String::from_utf8(vec).map_err(|_| Error::BadEncoding(self.offset))?;
+        self.offset += len + 1;
         Ok(string)
     }
 }
+
+/// Reads until `byte` like `BufRead::read_until`,
+/// but excludes `byte` from the result and fails if the input ends before `byte`.
+fn read_until_delimiter(read: &mut impl BufRead, byte: u8) -> IoResult<Vec<u8>> {
+    let mut vec = vec![];
+    let _ = read.read_until(byte, &mut vec)?;
+    if vec.pop() != Some(byte) {
+        return Err(Error::UnexpectedEof.into());
+    }
+    Ok(vec)
+}
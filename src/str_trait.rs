@@ -15,11 +15,33 @@ pub unsafe trait Str<'de>: 'de + Sized {
     /// Express the string as a slice of bytes
     fn as_bytes(&self) -> &[u8];
 
+    /// Returns whether the offset `i` is a boundary,
+    /// i.e. whether `range` and `clone_slice` may start or end at `i`.
+    ///
+    /// Offsets greater than `len()` are not boundaries.
+    ///
+    /// The default implementation clones the slice up to `i` to check it,
+    /// so implementations should override it with a cheaper check.
+    fn is_boundary(&self, i: usize) -> bool {
+        if i == 0 || i == self.len() {
+            return true;
+        }
+        // 0 is always a boundary, and both offsets are less than `len()`
+        i < self.len() && unsafe { self.clone_slice(0, i) }.is_some()
+    }
+
     /// Returns whether the string is empty.
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Returns the underlying bytes if the string borrows them for the lifetime `'de`.
+    ///
+    /// This allows zero-copy consumers to retain references into the input.
+    fn borrowed_bytes(&self) -> Option<&'de [u8]> {
+        None
+    }
+
     /// Gets the character at offset `i`.
     /// Returns `None` if `i+1` is not a boundary.
     ///
@@ -78,11 +100,20 @@ pub unsafe trait Str<'de>: 'de + Sized {
 
 unsafe impl<'de> Str<'de> for &'de str {
     fn len(&self) -> usize {
-        str::len(*self)
+        str::len(self)
     }
 
     fn as_bytes(&self) -> &[u8] {
-        str::as_bytes(*self)
+        str::as_bytes(self)
+    }
+
+    fn is_boundary(&self, i: usize) -> bool {
+        self.is_char_boundary(i)
+    }
+
+    fn borrowed_bytes(&self) -> Option<&'de [u8]> {
+        let str: &'de str = self;
+        Some(str.as_bytes())
     }
 
     unsafe fn get_u8_char(&self, i: usize) -> Option<u8> {
@@ -103,7 +134,7 @@ unsafe impl<'de> Str<'de> for &'de str {
         debug_assert!(self.is_char_boundary(i));
 
         if self.is_char_boundary(j) {
-            let bytes = str::as_bytes(*self).get_unchecked(i..j);
+            let bytes = str::as_bytes(self).get_unchecked(i..j);
             Some(str::from_utf8_unchecked(bytes)) // checked above
         } else {
             None
@@ -140,6 +171,10 @@ unsafe impl<'de> Str<'de> for String {
         str::as_bytes(self.as_str())
     }
 
+    fn is_boundary(&self, i: usize) -> bool {
+        self.is_char_boundary(i)
+    }
+
     unsafe fn get_u8_char(&self, i: usize) -> Option<u8> {
         self.as_str().get_u8_char(i)
     }
@@ -163,11 +198,19 @@ unsafe impl<'de> Str<'de> for String {
 
 unsafe impl<'de> Str<'de> for &'de [u8] {
     fn len(&self) -> usize {
-        <[u8]>::len(*self)
+        <[u8]>::len(self)
     }
 
     fn as_bytes(&self) -> &[u8] {
-        *self
+        self
+    }
+
+    fn is_boundary(&self, i: usize) -> bool {
+        i <= self.len()
+    }
+
+    fn borrowed_bytes(&self) -> Option<&'de [u8]> {
+        Some(*self)
     }
 
     unsafe fn get_u8_char(&self, i: usize) -> Option<u8> {
@@ -214,6 +257,10 @@ unsafe impl<'de> Str<'de> for Vec<u8> {
         self.as_slice()
     }
 
+    fn is_boundary(&self, i: usize) -> bool {
+        i <= self.len()
+    }
+
     unsafe fn get_u8_char(&self, i: usize) -> Option<u8> {
         self.as_slice().get_u8_char(i)
     }
@@ -1,5 +1,7 @@
 use derive_new::new;
-use getset::Getters;
+use getset::{CopyGetters, Getters};
+
+use crate::*;
 
 /// A serialized PHP value.
 #[derive(Debug, Clone)]
@@ -20,7 +22,10 @@ pub enum Value<S> {
     Object(Object<S>),
     /// Corresponds to `Serializable` objects in PHP.
     Serializable(Serializable<S>),
-    /// Corresponds to an internally-referenced value.
+    /// Corresponds to enum cases in PHP 8.1 and above.
+    Enum(EnumCase<S>),
+    /// Corresponds to an internally-referenced value,
+    /// either another handle to an object or a PHP reference.
     Reference(Ref),
 }
 
@@ -33,28 +38,106 @@ pub enum ArrayKey<S> {
     String(S),
 }
 
+impl<'de, S: Str<'de>> ArrayKey<S> {
+    /// Creates an array key from a string,
+    /// converting it to an int key if PHP would do so.
+    ///
+    /// PHP stores string keys that are canonical decimal integers,
+    /// such as `"123"` or `"-5"` but not `"0123"` or `"-0"`,
+    /// as int keys.
+    pub fn from_str_key(string: S) -> Self {
+        match parse_int_key(string.as_bytes()) {
+            Some(int) => ArrayKey::Int(int),
+            None => ArrayKey::String(string),
+        }
+    }
+}
+
+/// Parses a string key in the same way as `ZEND_HANDLE_NUMERIC_STR`.
+pub(crate) fn parse_int_key(bytes: &[u8]) -> Option<i64> {
+    let digits = match bytes.split_first() {
+        Some((b'-', digits)) => digits,
+        _ => bytes,
+    };
+    let canonical = match digits.split_first() {
+        Some((b'0', rest)) => rest.is_empty() && digits.len() == bytes.len(),
+        Some(_) => digits.iter().all(u8::is_ascii_digit),
+        None => false,
+    };
+    if !canonical {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
 /// A non-`Serializable` PHP object.
-#[derive(Debug, Clone, Getters, new)]
+#[derive(Debug, Clone, CopyGetters, Getters, new)]
 pub struct Object<S> {
     /// The object class.
-    #[getset(get)]
+    ///
+    /// For incomplete objects, this is the original class
+    /// rather than `__PHP_Incomplete_Class`.
+    #[getset(get = "pub")]
     class: S,
     /// The object properties.
-    #[getset(get)]
+    ///
+    /// For incomplete objects, this excludes the `__PHP_Incomplete_Class_Name` property.
+    #[getset(get = "pub")]
     properties: Vec<(PropertyName<S>, Value<S>)>,
+    /// Whether this is an `__PHP_Incomplete_Class` object,
+    /// which PHP creates when the original class is unavailable or disallowed.
+    ///
+    /// Incomplete objects are serialized as `__PHP_Incomplete_Class`
+    /// with the original class in the `__PHP_Incomplete_Class_Name` property,
+    /// followed by the other properties.
+    /// This includes downgraded `Serializable` objects,
+    /// whose original `C:` payload is not kept.
+    #[new(default)]
+    #[getset(get_copy = "pub")]
+    incomplete: bool,
 }
 
+impl<S> Object<S> {
+    /// Creates an incomplete object of the original class `class`.
+    pub fn new_incomplete(class: S, properties: Vec<(PropertyName<S>, Value<S>)>) -> Self {
+        Self {
+            class,
+            properties,
+            incomplete: true,
+        }
+    }
+
+    /// Consumes the object and returns the class and the properties.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(self) -> (S, Vec<(PropertyName<S>, Value<S>)>) {
+        (self.class, self.properties)
+    }
+}
+
+/// The class that PHP uses for objects whose class is unavailable or disallowed.
+pub(crate) const INCOMPLETE_CLASS: &str = "__PHP_Incomplete_Class";
+
+/// The property of an incomplete object that holds the original class name.
+pub(crate) const INCOMPLETE_CLASS_NAME: &str = "__PHP_Incomplete_Class_Name";
+
 /// The property name of an object.
 #[derive(Debug, Clone, Getters, new)]
 pub struct PropertyName<S> {
     /// Visibility of the property
-    #[getset(get)]
+    #[getset(get = "pub")]
     vis: PropertyVis<S>,
     /// Name of the property
-    #[getset(get)]
+    #[getset(get = "pub")]
     name: S,
 }
 
+impl<S> PropertyName<S> {
+    /// Consumes the property name and returns the name without the visibility.
+    pub fn into_name(self) -> S {
+        self.name
+    }
+}
+
 /// The visibility of an object property.
 #[derive(Debug, Clone)]
 pub enum PropertyVis<S> {
@@ -71,12 +154,78 @@ pub enum PropertyVis<S> {
 /// A PHP object that implements `Serializable`.
 #[derive(Debug, Clone, Getters, new)]
 pub struct Serializable<S> {
-    #[getset(get)]
+    /// The object class.
+    #[getset(get = "pub")]
     class: S,
-    #[getset(get)]
+    /// The data returned by `Serializable::serialize()`.
+    #[getset(get = "pub")]
     data: S,
 }
 
+impl<S> Serializable<S> {
+    /// Consumes the object and returns the class and the data.
+    pub fn into_parts(self) -> (S, S) {
+        (self.class, self.data)
+    }
+}
+
+/// A PHP 8.1 enum case.
+#[derive(Debug, Clone, Getters, new)]
+pub struct EnumCase<S> {
+    /// The enum class.
+    #[getset(get = "pub")]
+    class: S,
+    /// The name of the case.
+    #[getset(get = "pub")]
+    case: S,
+}
+
+impl<S> EnumCase<S> {
+    /// Consumes the enum case and returns the name of the case.
+    pub fn into_case(self) -> S {
+        self.case
+    }
+}
+
 /// A reference to another value in the serialized value tree.
-#[derive(Debug, Clone, Copy, new)]
-pub struct Ref(usize);
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct Ref {
+    /// Whether this is an object reference (`r:`) or a PHP reference (`R:`).
+    #[getset(get_copy = "pub")]
+    kind: RefKind,
+    /// The 1-based index of the referenced value in the serialized value tree.
+    #[getset(get_copy = "pub")]
+    index: usize,
+}
+
+impl Ref {
+    /// Creates a PHP reference (`R:`) to the value at the 1-based `index`.
+    pub fn new(index: usize) -> Self {
+        Self::with_kind(RefKind::Value, index)
+    }
+
+    /// Creates an object reference (`r:`) to the object at the 1-based `index`.
+    pub fn new_object(index: usize) -> Self {
+        Self::with_kind(RefKind::Object, index)
+    }
+
+    /// Creates a reference of the specified kind to the value at the 1-based `index`.
+    pub fn with_kind(kind: RefKind, index: usize) -> Self {
+        Self { kind, index }
+    }
+}
+
+/// The kind of a `Ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefKind {
+    /// Serialized as `r:`.
+    ///
+    /// The value is another handle to the same object instance,
+    /// emitted when an object appears more than once in the graph.
+    Object,
+    /// Serialized as `R:`.
+    ///
+    /// The value is a PHP reference (`&`) to the same variable,
+    /// so assigning to either location is visible through the other.
+    Value,
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use phpser::*;
+
+fn options(
+    allowed_classes: ClassPolicy,
+    disallowed_classes: DisallowedClassAction,
+) -> ParseOptions {
+    ParseOptions {
+        allowed_classes,
+        disallowed_classes,
+        ..ParseOptions::default()
+    }
+}
+
+fn allows(policy: &ClassPolicy, class: &str) -> bool {
+    let input = format!("O:{}:\"{}\":0:{{}}", class.len(), class);
+    let options = options(policy.clone(), DisallowedClassAction::Reject);
+    match Value::parse_with(input.as_str(), &options) {
+        Ok(_) => true,
+        Err(IoError::Phpser(Error::DisallowedClass(_))) => false,
+        Err(err) => panic!("{}: {:?}", input, err),
+    }
+}
+
+fn is_foo(class: &[u8]) -> bool {
+    class == b"Foo"
+}
+
+#[test]
+fn class_policies() {
+    let all = ClassPolicy::All;
+    assert!(allows(&all, "Foo"));
+    assert!(allows(&all, "stdClass"));
+
+    let none = ClassPolicy::None;
+    assert!(!allows(&none, "Foo"));
+    assert!(!allows(&none, "stdClass"));
+
+    let list = ClassPolicy::List(vec!["Foo".into(), "app\\bar".into()]);
+    assert!(allows(&list, "Foo"));
+    assert!(allows(&list, "FOO"));
+    assert!(allows(&list, "App\\Bar"));
+    assert!(!allows(&list, "Fo"));
+    assert!(!allows(&list, "Baz"));
+
+    let function = ClassPolicy::Predicate(Arc::new(is_foo));
+    assert!(allows(&function, "Foo"));
+    assert!(!allows(&function, "foo"));
+
+    let namespace = String::from("App\\");
+    let closure = ClassPolicy::predicate(move |class| class.starts_with(namespace.as_bytes()));
+    assert!(allows(&closure, "App\\Foo"));
+    assert!(!allows(&closure, "Foo"));
+    assert_eq!(format!("{:?}", closure), "Predicate(..)");
+}
+
+#[test]
+fn reject() {
+    let options = options(
+        ClassPolicy::List(vec!["Foo".into()]),
+        DisallowedClassAction::Reject,
+    );
+    for (input, offset) in &[
+        ("O:3:\"Bar\":0:{}", 0),
+        ("a:1:{i:0;O:3:\"Foo\":1:{s:1:\"a\";O:3:\"Bar\":0:{}}}", 30),
+        ("C:3:\"Bar\":3:{abc}", 0),
+        ("a:1:{i:0;C:3:\"Bar\":3:{abc}}", 9),
+    ] {
+        match Value::parse_with(*input, &options) {
+            Err(IoError::Phpser(Error::DisallowedClass(actual))) => {
+                assert_eq!(actual, *offset, "{}", input)
+            }
+            result => panic!("{} parsed as {:?}", input, result),
+        }
+    }
+    assert!(Value::parse_with("C:3:\"Foo\":3:{abc}", &options).is_ok());
+    assert!(Value::parse_with("E:7:\"Bar:Baz\";", &options).is_ok());
+
+    let igb = Value::parse("a:1:{i:0;O:3:\"Bar\":0:{}}")
+        .expect("valid input")
+        .to_igbinary()
+        .expect("encodable");
+    assert_eq!(igb.get(8), Some(&0x17));
+    assert!(matches!(
+        Value::parse_igbinary_with(&igb[..], &options),
+        Err(IoError::Phpser(Error::DisallowedClass(8)))
+    ));
+}
+
+#[test]
+fn incomplete() {
+    let options = options(
+        ClassPolicy::List(vec!["Foo".into()]),
+        DisallowedClassAction::Incomplete,
+    );
+
+    let value = Value::parse_with("O:3:\"Bar\":1:{s:1:\"a\";O:3:\"Foo\":0:{}}", &options)
+        .expect("valid input");
+    let object = match &value {
+        Value::Object(object) => object,
+        value => panic!("parsed as {:?}", value),
+    };
+    assert!(object.incomplete());
+    assert_eq!(*object.class(), "Bar");
+    match object.properties().as_slice() {
+        [(name, Value::Object(inner))] => {
+            assert_eq!(*name.name(), "a");
+            assert!(!inner.incomplete());
+            assert_eq!(*inner.class(), "Foo");
+        }
+        properties => panic!("properties {:?}", properties),
+    }
+    assert_eq!(
+        value.to_bytes(),
+        &b"O:22:\"__PHP_Incomplete_Class\":2:{s:27:\"__PHP_Incomplete_Class_Name\";s:3:\"Bar\";s:1:\"a\";O:3:\"Foo\":0:{}}"[..]
+    );
+
+    let value = Value::parse_with("a:1:{i:0;C:3:\"Bar\":3:{abc}}", &options).expect("valid input");
+    match &value {
+        Value::Array(entries) => match entries.as_slice() {
+            [(ArrayKey::Int(0), Value::Object(object))] => {
+                assert!(object.incomplete());
+                assert_eq!(*object.class(), "Bar");
+                assert!(object.properties().is_empty());
+            }
+            entries => panic!("entries {:?}", entries),
+        },
+        value => panic!("parsed as {:?}", value),
+    }
+    let serializable = Value::parse_with("C:3:\"Foo\":3:{abc}", &options).expect("valid input");
+    assert!(matches!(serializable, Value::Serializable(_)));
+}
+
+#[test]
+fn default_options_allow_all_classes() {
+    let options = ParseOptions::default();
+    assert!(options.allowed_classes.allows(b"Foo"));
+    assert_eq!(
+        options.disallowed_classes,
+        DisallowedClassAction::Incomplete
+    );
+}
+
+const INCOMPLETE: &str = "O:22:\"__PHP_Incomplete_Class\":2:{s:27:\"__PHP_Incomplete_Class_Name\";s:3:\"Foo\";s:1:\"a\";i:1;}";
+
+#[test]
+fn incomplete_round_trip() {
+    for options in &[
+        ParseOptions::default(),
+        options(ClassPolicy::None, DisallowedClassAction::Incomplete),
+        options(
+            ClassPolicy::List(vec!["Foo".into()]),
+            DisallowedClassAction::Incomplete,
+        ),
+    ] {
+        // PHP class names are case-insensitive
+        let lowercase = INCOMPLETE.replacen("__PHP_Incomplete_Class", "__php_incomplete_class", 1);
+        for input in &[INCOMPLETE, lowercase.as_str()] {
+            let value = Value::parse_with(*input, options).expect("valid input");
+            match &value {
+                Value::Object(object) => {
+                    assert!(object.incomplete(), "{}", input);
+                    assert_eq!(*object.class(), "Foo");
+                    match object.properties().as_slice() {
+                        [(name, Value::Int(1))] => assert_eq!(*name.name(), "a"),
+                        properties => panic!("properties {:?}", properties),
+                    }
+                }
+                value => panic!("parsed as {:?}", value),
+            }
+            assert_eq!(value.to_bytes(), INCOMPLETE.as_bytes(), "{:?}", options);
+        }
+    }
+
+    let constructed = Value::Object(Object::new_incomplete(
+        "Foo",
+        vec![(PropertyName::new(PropertyVis::Public, "a"), Value::Int(1))],
+    ));
+    assert_eq!(constructed.to_bytes(), INCOMPLETE.as_bytes());
+}
+
+#[test]
+fn incomplete_without_class_name() {
+    let input = "O:22:\"__PHP_Incomplete_Class\":1:{s:1:\"a\";i:1;}";
+    let value = Value::parse(input).expect("valid input");
+    match &value {
+        Value::Object(object) => {
+            assert!(!object.incomplete());
+            assert_eq!(*object.class(), "__PHP_Incomplete_Class");
+        }
+        value => panic!("parsed as {:?}", value),
+    }
+    assert_eq!(value.to_bytes(), input.as_bytes());
+}
+
+#[test]
+fn downgraded_serializable_round_trip() {
+    let options = options(ClassPolicy::None, DisallowedClassAction::Incomplete);
+    let downgraded = Value::parse_with("C:3:\"Foo\":3:{abc}", &options).expect("valid input");
+    let emitted = downgraded.to_bytes();
+    assert_eq!(
+        emitted,
+        &b"O:22:\"__PHP_Incomplete_Class\":1:{s:27:\"__PHP_Incomplete_Class_Name\";s:3:\"Foo\";}"[..]
+    );
+
+    let reparsed = Value::parse_with(&emitted[..], &options).expect("valid input");
+    match &reparsed {
+        Value::Object(object) => {
+            assert!(object.incomplete());
+            assert_eq!(*object.class(), &b"Foo"[..]);
+            assert!(object.properties().is_empty());
+        }
+        value => panic!("parsed as {:?}", value),
+    }
+    assert_eq!(reparsed.to_bytes(), emitted);
+}
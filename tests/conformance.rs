@@ -0,0 +1,195 @@
+//! Checks that every `Source` implementation behaves identically for the same input.
+
+use phpser::*;
+
+/// The observable result of parsing one value from a source.
+///
+/// Successful parses are compared by their reserialization and the source offset after the value;
+/// failures are compared by the reported error.
+type Outcome = Result<(Vec<u8>, usize), String>;
+
+fn outcome<'de, S: Str<'de>>(mut source: impl Source<'de, S>) -> Outcome {
+    match Value::from_source(&mut source) {
+        Ok(value) => Ok((value.to_bytes(), source.offset())),
+        Err(IoError::Phpser(err)) => Err(format!("{:?}", err)),
+        Err(IoError::Io(err)) => Err(format!("io: {:?}", err.kind())),
+    }
+}
+
+fn byte_outcomes(input: &[u8]) -> Vec<(&'static str, Outcome)> {
+    vec![
+        ("Cursor<&[u8]>", outcome(Cursor::new(input))),
+        ("ByteReader", outcome(ByteReader::new(input, input.len()))),
+    ]
+}
+
+fn str_outcomes(input: &str) -> Vec<(&'static str, Outcome)> {
+    vec![
+        ("Cursor<&str>", outcome(Cursor::new(input))),
+        (
+            "StringReader",
+            outcome(StringReader::new(input.as_bytes(), input.len())),
+        ),
+    ]
+}
+
+fn assert_agree(input: &[u8], outcomes: &[(&'static str, Outcome)]) -> Outcome {
+    let (reference_name, reference) = outcomes.first().expect("at least one source");
+    for (name, outcome) in outcomes {
+        assert_eq!(
+            outcome,
+            reference,
+            "{} disagrees with {} on {:?}",
+            name,
+            reference_name,
+            String::from_utf8_lossy(input),
+        );
+    }
+    reference.clone()
+}
+
+/// Asserts that all four sources agree on `input` and returns the common outcome.
+fn check(input: &str) -> Outcome {
+    let mut outcomes = byte_outcomes(input.as_bytes());
+    outcomes.extend(str_outcomes(input));
+    assert_agree(input.as_bytes(), &outcomes)
+}
+
+const INPUTS: &[&str] = &[
+    // scalars
+    "N;",
+    "b:0;",
+    "b:1;",
+    "i:0;",
+    "i:-42;",
+    "i:9223372036854775807;",
+    "d:1.5;",
+    "d:-0.25;",
+    "d:INF;",
+    "d:-INF;",
+    "d:NAN;",
+    "s:0:\"\";",
+    "s:5:\"hello\";",
+    "s:3:\"a;b\";",
+    "s:2:\"\u{e9}\";",
+    // compound values
+    "a:0:{}",
+    "a:2:{i:0;s:1:\"a\";s:1:\"k\";b:0;}",
+    "a:1:{i:0;a:1:{i:0;a:0:{}}}",
+    "O:8:\"stdClass\":0:{}",
+    "O:8:\"stdClass\":2:{s:1:\"a\";i:1;s:4:\"\0*\0b\";N;}",
+    "C:3:\"Foo\":3:{abc}",
+    "E:7:\"Foo:Bar\";",
+    "a:2:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;}",
+    "a:2:{i:0;i:1;i:1;R:2;}",
+    // trailing data is left in the source
+    "i:1;i:2;",
+    "N;garbage",
+    // truncated input
+    "",
+    "i",
+    "i:",
+    "i:1",
+    "s:5:\"hel",
+    "s:5:\"hello\"",
+    "a:1:{i:0;",
+    "a:1:{i:0;i:1;",
+    "O:8:\"stdClass",
+    "C:3:\"Foo\":3:{ab",
+    "E:7:\"Foo:Bar",
+    // malformed input
+    "x;",
+    "N:",
+    "b:2;",
+    "i:;",
+    "i:1x;",
+    "i:99999999999999999999;",
+    "d:abc;",
+    "s:x:\"\";",
+    "s:1:\"ab\";",
+    "s:2:\"a\";",
+    "a:1:[i:0;i:1;}",
+    "a:1:{d:1.5;i:1;}",
+    "a:1:{i:0;i:1;]",
+    "a:2:{i:0;i:1;}",
+    "O:8:\"stdClass\":1:{i:0;i:1;}",
+    "E:3:\"Foo\";",
+    "r:0;",
+];
+
+/// Inputs on which byte sources and string sources legitimately differ,
+/// because string sources also validate the encoding.
+const ENCODING_INPUTS: &[&str] = &[
+    // non-ASCII where a token is expected
+    "\u{e9}",
+    "i:\u{e9};",
+    // string lengths splitting a character
+    "s:1:\"\u{e9}\";",
+    "a:1:{s:1:\"\u{e9}\";i:1;}",
+];
+
+#[test]
+fn all_sources_agree() {
+    for input in INPUTS {
+        let _ = check(input);
+    }
+}
+
+#[test]
+fn sources_of_same_kind_agree_on_encoding() {
+    for input in ENCODING_INPUTS {
+        let _ = assert_agree(input.as_bytes(), &byte_outcomes(input.as_bytes()));
+        let _ = assert_agree(input.as_bytes(), &str_outcomes(input));
+    }
+}
+
+#[test]
+fn byte_sources_agree_on_invalid_utf8() {
+    for input in &[
+        &b"s:1:\"\xff\";"[..],
+        b"s:2:\"\xc3\";",
+        b"a:1:{s:1:\"\xff\";i:1;}",
+        b"\xff",
+        b"i:\xff;",
+    ] {
+        let _ = assert_agree(input, &byte_outcomes(input));
+    }
+}
+
+#[test]
+fn offsets_after_value() {
+    assert_eq!(check("N;"), Ok((b"N;".to_vec(), 2)));
+    assert_eq!(check("i:1;i:2;"), Ok((b"i:1;".to_vec(), 4)));
+    assert_eq!(check("s:3:\"a;b\";N;"), Ok((b"s:3:\"a;b\";".to_vec(), 10)),);
+    assert_eq!(
+        check("a:1:{i:0;d:1.5;}N;"),
+        Ok((b"a:1:{i:0;d:1.5;}".to_vec(), 16)),
+    );
+}
+
+#[test]
+fn error_offsets() {
+    assert_eq!(check("x;"), Err("BadToken(1)".to_owned()));
+    assert_eq!(check("a:1:{i:0;i:1;]"), Err("BadToken(14)".to_owned()));
+    assert_eq!(check("s:1:\"ab\";"), Err("BadToken(7)".to_owned()));
+    assert_eq!(check("s:5:\"hel"), Err("UnexpectedEof".to_owned()));
+}
+
+#[test]
+fn string_sources_validate_encoding() {
+    let input = "s:1:\"\u{e9}\";";
+    assert_eq!(
+        assert_agree(input.as_bytes(), &str_outcomes(input)),
+        Err("BadEncoding(5)".to_owned()),
+    );
+    let input = "N;\u{e9}";
+    assert_eq!(
+        assert_agree(input.as_bytes(), &str_outcomes(input)),
+        Ok((b"N;".to_vec(), 2)),
+    );
+    let input = "\u{e9}";
+    assert_eq!(
+        assert_agree(input.as_bytes(), &str_outcomes(input)),
+        Err("BadEncoding(0)".to_owned()),
+    );
+}
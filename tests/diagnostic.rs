@@ -0,0 +1,59 @@
+use phpser::*;
+
+fn detailed_error(input: &str) -> ParseError {
+    match Value::parse_detailed(input) {
+        Ok(_) => panic!("{:?} should not parse", input),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn path_and_expectation() {
+    let input = "a:1:{s:4:\"cart\";a:2:{i:0;N;i:1;O:8:\"stdClass\":1:{s:5:\"price\";d:1.5x;}}}";
+    let err = detailed_error(input);
+    assert!(matches!(err.error(), Error::BadNumber(_)));
+    assert_eq!(err.path().to_string(), "[\"cart\"][1]->price");
+    assert_eq!(err.expected(), Some(Expected::Number(b';')));
+    assert_eq!(err.offset(), 63);
+    assert_eq!(err.found(), Some(b'1'));
+}
+
+#[test]
+fn path_excludes_finished_entries() {
+    let err = detailed_error("a:2:{i:0;a:0:{}i:1;a:1:{s:1:\"k\";Z;}}");
+    assert_eq!(err.path().to_string(), "[1][\"k\"]");
+    assert_eq!(err.expected(), Some(Expected::Value));
+    assert_eq!(err.found(), Some(b'Z'));
+}
+
+#[test]
+fn unexpected_eof() {
+    let err = detailed_error("a:1:{i:0;i:1;");
+    assert!(matches!(err.error(), Error::UnexpectedEof));
+    assert_eq!(err.offset(), 13);
+    assert_eq!(err.expected(), Some(Expected::Char(b'}')));
+    assert_eq!(err.found(), None);
+}
+
+#[test]
+fn render_caret() {
+    let input = "a:1:{i:0;i:1;]";
+    assert_eq!(
+        detailed_error(input).render(input.as_bytes()),
+        "encountered invalid token at offset 13: expected `}`, found `]`\n  \
+         |\n  \
+         | a:1:{i:0;i:1;]\n  \
+         |              ^\n",
+    );
+}
+
+#[test]
+fn render_long_input() {
+    let input = format!("a:1:{{i:0;s:40:\"{}\";x}}", "y".repeat(40));
+    let rendered = detailed_error(&input).render(input.as_bytes());
+    assert!(rendered.ends_with(&format!(
+        "  | ...{}\";x}}\n  | {}^\n",
+        "y".repeat(30),
+        " ".repeat(35),
+    )));
+}
@@ -0,0 +1,119 @@
+use phpser::*;
+
+const ROUND_TRIPS: &[&[u8]] = &[
+    b"N;",
+    b"b:0;",
+    b"b:1;",
+    b"i:0;",
+    b"i:-9223372036854775808;",
+    b"s:0:\"\";",
+    b"s:6:\"a\";b\"}\";",
+    b"s:2:\"\xff\x00\";",
+    b"a:0:{}",
+    b"a:3:{i:-1;N;s:1:\"k\";s:1:\"v\";s:2:\"01\";a:1:{i:0;b:1;}}",
+    b"O:8:\"stdClass\":0:{}",
+    b"O:3:\"Foo\":3:{s:1:\"a\";i:1;s:4:\"\0*\0b\";i:2;s:6:\"\0Foo\0c\";i:3;}",
+    b"O:3:\"Foo\":1:{s:8:\"\0Bar\0\0*\0\";N;}",
+    b"C:3:\"Foo\":4:{a;b}}",
+    b"a:2:{i:0;i:1;i:1;R:2;}",
+];
+
+#[test]
+fn parsed_values_round_trip() {
+    for &input in ROUND_TRIPS {
+        let value = Value::parse(input)
+            .unwrap_or_else(|err| panic!("{:?}: {:?}", String::from_utf8_lossy(input), err));
+        assert_eq!(
+            value.to_bytes(),
+            input,
+            "{:?}",
+            String::from_utf8_lossy(input)
+        );
+
+        let mut written = vec![];
+        value.write_to(&mut written).expect("write to Vec<u8>");
+        assert_eq!(written, input);
+
+        let mut emitted = b"prefix".to_vec();
+        value.emit(&mut emitted);
+        assert_eq!(&emitted[6..], input);
+    }
+}
+
+#[test]
+fn constructed_values() {
+    let object = Value::Object(Object::new(
+        "Foo",
+        vec![
+            (PropertyName::new(PropertyVis::Public, "a"), Value::Int(1)),
+            (PropertyName::new(PropertyVis::Protected, "b"), Value::Null),
+            (
+                PropertyName::new(PropertyVis::Private("Bar"), "c"),
+                Value::Serializable(Serializable::new("Baz", "xyz")),
+            ),
+        ],
+    ));
+    let value = Value::Array(vec![
+        (ArrayKey::Int(5), object),
+        (ArrayKey::String("r"), Value::Reference(Ref::new(2))),
+    ]);
+    assert_eq!(
+        value.to_bytes(),
+        &b"a:2:{i:5;O:3:\"Foo\":3:{s:1:\"a\";i:1;s:4:\"\0*\0b\";N;s:6:\"\0Bar\0c\";C:3:\"Baz\":3:{xyz}}s:1:\"r\";R:2;}"[..]
+    );
+}
+
+fn float(float: f64, float_precision: FloatPrecision) -> String {
+    let mut buf = vec![];
+    Value::<&str>::Float(float).emit_with(&mut buf, &EmitOptions { float_precision });
+    String::from_utf8(buf).expect("ASCII output")
+}
+
+#[test]
+fn float_format() {
+    for &(input, expected) in &[
+        (f64::INFINITY, "d:INF;"),
+        (f64::NEG_INFINITY, "d:-INF;"),
+        (f64::NAN, "d:NAN;"),
+        (0.0, "d:0;"),
+        (-0.0, "d:-0;"),
+        (1.0, "d:1;"),
+        (-1.5, "d:-1.5;"),
+        (0.1, "d:0.1;"),
+        (0.0001, "d:0.0001;"),
+        (0.00001, "d:1.0E-5;"),
+        (1e25, "d:1.0E+25;"),
+        (1.5e25, "d:1.5E+25;"),
+        (123456789012345.0, "d:123456789012345;"),
+        (f64::MAX, "d:1.7976931348623157E+308;"),
+    ] {
+        assert_eq!(
+            float(input, FloatPrecision::Shortest),
+            expected,
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn float_format_digits() {
+    for &(input, digits, expected) in &[
+        (0.1, 17, "d:0.10000000000000001;"),
+        (1.5, 17, "d:1.5;"),
+        (1e25, 17, "d:1.0000000000000001E+25;"),
+        (-0.0, 17, "d:-0;"),
+        (f64::INFINITY, 17, "d:INF;"),
+        (0.1, 1, "d:0.1;"),
+        (123.456, 2, "d:1.2E+2;"),
+        (123.456, 0, "d:1.0E+2;"),
+    ] {
+        assert_eq!(
+            float(input, FloatPrecision::Digits(digits)),
+            expected,
+            "{} with {} digits",
+            input,
+            digits
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use std::error::Error as StdError;
+use std::io::{self, Read};
+
+use phpser::*;
+
+struct FailingRead;
+
+impl Read for FailingRead {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("disk on fire"))
+    }
+}
+
+fn parse_all() -> Result<(), Box<dyn StdError>> {
+    let _ = Value::parse("i:1;")?;
+    let _ = Value::parse(&b"i:1;"[..])?;
+    let _ = Value::from_source(ByteReader::new(&b"i:1;"[..], 4))?;
+    let _ = Value::from_source(StringReader::new(&b"i:1"[..], 3))?;
+    Ok(())
+}
+
+#[test]
+fn propagates_into_box_dyn_error() {
+    let err = parse_all().expect_err("the last value is truncated");
+    assert_eq!(err.to_string(), "unexpected end of document");
+}
+
+#[test]
+fn io_error_is_source() {
+    let err = Value::from_source(ByteReader::new(FailingRead, 16)).expect_err("read fails");
+    assert_eq!(err.to_string(), "disk on fire");
+    let source = err.source().expect("IO errors have a source");
+    assert_eq!(source.to_string(), "disk on fire");
+}
+
+#[test]
+fn converts_to_io_error() {
+    let err: io::Error = Value::parse("x").expect_err("bad token").into();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "encountered invalid token at offset 1");
+
+    let err: io::Error = Value::parse("i:1").expect_err("truncated").into();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,153 @@
+use phpser::*;
+
+fn events(input: &str, options: &ParseOptions) -> Vec<(usize, String)> {
+    EventParser::with_options(Cursor::new(input), options)
+        .map(|event| {
+            let event = event.expect("valid input");
+            (event.offset(), format!("{:?}", event.kind()))
+        })
+        .collect()
+}
+
+fn assert_events(input: &str, options: &ParseOptions, expected: &[(usize, &str)]) {
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|&(offset, kind)| (offset, kind.to_string()))
+        .collect();
+    assert_eq!(events(input, options), expected, "{:?}", input);
+}
+
+#[test]
+fn event_sequence() {
+    assert_events(
+        "a:3:{i:0;O:3:\"Foo\":2:{s:4:\"\0*\0b\";d:1.5;s:1:\"c\";N;}s:1:\"k\";a:1:{i:0;R:2;}i:1;C:3:\"Bar\":2:{xy}}",
+        &ParseOptions::default(),
+        &[
+            (0, "ArrayStart(3)"),
+            (5, "Key(Int(0))"),
+            (9, "ObjectStart { class: \"Foo\", len: 2, incomplete: false }"),
+            (22, "PropertyName(PropertyName { vis: Protected, name: \"b\" })"),
+            (33, "Scalar(Float(1.5))"),
+            (39, "PropertyName(PropertyName { vis: Public, name: \"c\" })"),
+            (47, "Scalar(Null)"),
+            (49, "End"),
+            (50, "Key(String(\"k\"))"),
+            (58, "ArrayStart(1)"),
+            (63, "Key(Int(0))"),
+            (67, "Reference(Ref { kind: Value, index: 2 })"),
+            (71, "End"),
+            (72, "Key(Int(1))"),
+            (76, "Serializable(Serializable { class: \"Bar\", data: \"xy\" })"),
+            (92, "End"),
+        ],
+    );
+}
+
+#[test]
+fn single_events() {
+    let defaults = ParseOptions::default();
+    assert_events("N;", &defaults, &[(0, "Scalar(Null)")]);
+    assert_events("s:2:\"ab\";", &defaults, &[(0, "Scalar(String(\"ab\"))")]);
+    assert_events(
+        "E:7:\"Foo:Bar\";",
+        &defaults,
+        &[(0, "Enum(EnumCase { class: \"Foo\", case: \"Bar\" })")],
+    );
+    assert_events("a:0:{}", &defaults, &[(0, "ArrayStart(0)"), (5, "End")]);
+}
+
+#[test]
+fn incomplete_events() {
+    let options = ParseOptions {
+        allowed_classes: ClassPolicy::None,
+        ..ParseOptions::default()
+    };
+    assert_events(
+        "a:2:{i:0;O:3:\"Foo\":1:{s:1:\"a\";i:1;}i:1;C:3:\"Bar\":2:{xy}}",
+        &options,
+        &[
+            (0, "ArrayStart(2)"),
+            (5, "Key(Int(0))"),
+            (
+                9,
+                "ObjectStart { class: \"Foo\", len: 1, incomplete: true }",
+            ),
+            (
+                22,
+                "PropertyName(PropertyName { vis: Public, name: \"a\" })",
+            ),
+            (30, "Scalar(Int(1))"),
+            (34, "End"),
+            (35, "Key(Int(1))"),
+            (
+                39,
+                "ObjectStart { class: \"Bar\", len: 0, incomplete: true }",
+            ),
+            (55, "End"),
+            (55, "End"),
+        ],
+    );
+}
+
+#[test]
+fn depth_and_read_value() {
+    let input = "a:2:{s:1:\"a\";a:1:{i:0;i:1;}s:1:\"b\";i:2;}tail";
+    let mut parser = EventParser::new(Cursor::new(input));
+    assert_eq!(parser.depth(), 0);
+    assert!(matches!(
+        parser.next_event().map(|event| event.map(Event::into_kind)),
+        Ok(Some(EventKind::ArrayStart(2)))
+    ));
+    assert_eq!(parser.depth(), 1);
+    assert!(matches!(
+        parser.next_event().map(|event| event.map(Event::into_kind)),
+        Ok(Some(EventKind::Key(ArrayKey::String("a"))))
+    ));
+    let value = parser.read_value().expect("valid input");
+    assert_eq!(value.to_bytes(), b"a:1:{i:0;i:1;}");
+    assert_eq!(parser.depth(), 1);
+
+    let rest: Vec<_> = parser
+        .by_ref()
+        .map(|event| event.map(|event| (event.offset(), format!("{:?}", event.kind()))))
+        .collect::<IoResult<_>>()
+        .expect("valid input");
+    assert_eq!(
+        rest,
+        vec![
+            (27, "Key(String(\"b\"))".to_string()),
+            (35, "Scalar(Int(2))".to_string()),
+            (39, "End".to_string()),
+        ]
+    );
+    assert_eq!(parser.depth(), 0);
+    assert!(matches!(parser.next_event(), Ok(None)));
+    assert_eq!(parser.into_source().remaining(), "tail");
+}
+
+#[test]
+#[should_panic(expected = "no value is expected")]
+fn read_value_requires_a_value() {
+    let mut parser = EventParser::new(Cursor::new("a:1:{i:0;N;}"));
+    let _ = parser.next_event();
+    let _ = parser.read_value();
+}
+
+#[test]
+fn errors_end_the_events() {
+    let mut parser = EventParser::new(Cursor::new("a:2:{i:0;N;i:0;x}"));
+    assert_eq!(parser.by_ref().take(4).filter(Result::is_ok).count(), 4);
+    assert!(matches!(
+        parser.next(),
+        Some(Err(IoError::Phpser(Error::BadToken(_))))
+    ));
+    assert!(parser.next().is_none());
+
+    let mut parser = EventParser::new(Cursor::new("a:1:{i:0;"));
+    assert_eq!(parser.by_ref().take(2).filter(Result::is_ok).count(), 2);
+    assert!(matches!(
+        parser.next(),
+        Some(Err(IoError::Phpser(Error::UnexpectedEof)))
+    ));
+    assert!(parser.next().is_none());
+}
@@ -0,0 +1,167 @@
+use phpser::*;
+
+fn resolve(input: &str) -> Graph<&str> {
+    Value::parse(input)
+        .expect("valid input")
+        .resolve()
+        .expect("valid references")
+}
+
+fn array_edges(graph: &Graph<&str>, id: NodeId) -> Vec<Edge> {
+    match graph.get(id) {
+        Some(Node::Array(entries)) => entries.iter().map(|&(_, edge)| edge).collect(),
+        node => panic!("{:?} is not an array", node),
+    }
+}
+
+#[test]
+fn shared_objects() {
+    let graph = resolve("a:3:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;i:2;O:8:\"stdClass\":0:{}}");
+    let edges = array_edges(&graph, graph.root());
+    let targets: Vec<_> = edges.iter().map(|edge| edge.target()).collect();
+    assert_eq!(targets.first(), targets.get(1));
+    assert_ne!(targets.first(), targets.get(2));
+    assert!(edges.iter().all(|edge| !edge.is_ref()));
+}
+
+#[test]
+fn value_references() {
+    let graph = resolve("a:3:{i:0;i:1;i:1;R:2;i:2;i:1;}");
+    let edges = array_edges(&graph, graph.root());
+    let flags: Vec<_> = edges.iter().map(|edge| edge.is_ref()).collect();
+    assert_eq!(flags, vec![true, true, false]);
+    assert_eq!(
+        edges.first().map(|edge| edge.target()),
+        edges.get(1).map(|edge| edge.target())
+    );
+    assert!(matches!(
+        edges.get(2).and_then(|edge| graph.get(edge.target())),
+        Some(Node::Int(1))
+    ));
+}
+
+#[test]
+fn cycles() {
+    let graph = resolve("a:1:{i:0;R:1;}");
+    let edges = array_edges(&graph, graph.root());
+    assert_eq!(edges.len(), 1);
+    let edge = edges.first().expect("one entry");
+    assert_eq!(edge.target(), graph.root());
+    assert!(edge.is_ref());
+
+    let object = resolve("O:8:\"stdClass\":1:{s:4:\"self\";r:1;}");
+    match object.get(object.root()) {
+        Some(Node::Object { properties, .. }) => {
+            let (_, edge) = properties.first().expect("one property");
+            assert_eq!(edge.target(), object.root());
+            assert!(!edge.is_ref());
+        }
+        node => panic!("root is {:?}", node),
+    }
+}
+
+#[test]
+fn slot_numbering() {
+    // keys and the `Serializable` payload take no slots
+    let graph = resolve("a:3:{s:1:\"k\";C:3:\"Foo\":4:{i:1;}i:0;a:1:{i:0;i:7;}i:1;R:4;}");
+    let edges = array_edges(&graph, graph.root());
+    let nested = edges.get(1).expect("nested array").target();
+    let inner = array_edges(&graph, nested);
+    assert_eq!(
+        inner.first().map(|edge| edge.target()),
+        edges.get(2).map(|edge| edge.target())
+    );
+
+    for (input, index) in &[
+        ("a:1:{i:0;R:0;}", 0),
+        ("a:1:{i:0;R:3;}", 3),
+        ("a:2:{i:0;r:3;i:1;N;}", 3),
+    ] {
+        let value = Value::parse(*input).expect("valid syntax");
+        match value.resolve() {
+            Err(Error::BadReference(actual)) => assert_eq!(actual, *index, "{}", input),
+            result => panic!("{:?} resolved as {:?}", input, result),
+        }
+    }
+}
+
+#[test]
+fn resolved_graphs_round_trip() {
+    for input in &[
+        "a:3:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;i:2;O:8:\"stdClass\":0:{}}",
+        "a:3:{i:0;i:1;i:1;R:2;i:2;i:1;}",
+        "a:1:{i:0;R:1;}",
+        "O:8:\"stdClass\":1:{s:4:\"self\";r:1;}",
+        "a:3:{s:1:\"k\";C:3:\"Foo\":4:{i:1;}i:0;a:1:{i:0;i:7;}i:1;R:4;}",
+        "a:2:{i:0;E:7:\"Foo:Bar\";i:1;r:2;}",
+    ] {
+        assert_eq!(resolve(input).to_bytes(), input.as_bytes(), "{}", input);
+    }
+}
+
+#[test]
+fn constructed_graphs() {
+    let mut graph: Graph<&str> = Graph::new(Node::Null);
+    let object = graph.add(Node::Object {
+        class: "Foo",
+        properties: vec![],
+        incomplete: false,
+    });
+    let list = graph.add(Node::Array(vec![(
+        ArrayKey::Int(0),
+        Edge::new(object, false),
+    )]));
+    let int = graph.add(Node::Int(5));
+    let root = graph.add(Node::Array(vec![
+        (ArrayKey::Int(0), Edge::new(object, false)),
+        (ArrayKey::Int(1), Edge::new(object, false)),
+        (ArrayKey::Int(2), Edge::new(list, false)),
+        (ArrayKey::Int(3), Edge::new(list, false)),
+        (ArrayKey::Int(4), Edge::new(int, true)),
+        (ArrayKey::Int(5), Edge::new(int, true)),
+    ]));
+    graph.set_root(root);
+    assert_eq!(
+        graph.to_bytes(),
+        concat!(
+            "a:6:{i:0;O:3:\"Foo\":0:{}i:1;r:2;",
+            "i:2;a:1:{i:0;r:2;}i:3;a:1:{i:0;r:2;}",
+            "i:4;i:5;i:5;R:8;}",
+        )
+        .as_bytes()
+    );
+}
+
+#[test]
+fn constructed_cycles() {
+    let mut graph: Graph<&str> = Graph::new(Node::Array(vec![]));
+    let root = graph.root();
+    if let Some(Node::Array(entries)) = graph.get_mut(root) {
+        entries.push((ArrayKey::String("self"), Edge::new(root, false)));
+    }
+    assert_eq!(graph.to_bytes(), b"a:1:{s:4:\"self\";R:1;}");
+
+    let mut buf = vec![];
+    graph.emit_with(
+        &mut buf,
+        &EmitOptions {
+            float_precision: FloatPrecision::Digits(17),
+        },
+    );
+    assert_eq!(buf, graph.to_bytes());
+}
+
+#[test]
+fn foreign_node() {
+    let other = resolve("a:2:{i:0;N;i:1;N;}");
+    let foreign = array_edges(&other, other.root())
+        .last()
+        .expect("two entries")
+        .target();
+    let graph: Graph<&str> = Graph::new(Node::Array(vec![(
+        ArrayKey::Int(0),
+        Edge::new(foreign, false),
+    )]));
+    let err = graph.write_to(vec![]).expect_err("foreign node");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
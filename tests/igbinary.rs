@@ -0,0 +1,118 @@
+//! The golden files in `tests/igbinary` pair the output of `igbinary_serialize` (`*.igb`)
+//! with the output of `serialize` (`*.ser`) for the same PHP value.
+//!
+//! `tests/igbinary/generate.php` captures them from PHP
+//! and records the PHP and igbinary versions in `tests/igbinary/VERSION`.
+//!
+//! There is no `VERSION` file yet:
+//! the current files were assembled by hand following the igbinary 2 format,
+//! so these tests only check the encoder and decoder against each other
+//! until the files are regenerated with a real PHP and igbinary build.
+
+use phpser::*;
+
+const GOLDEN: &[(&str, &[u8], &[u8])] = &[
+    (
+        "scalars",
+        include_bytes!("igbinary/scalars.igb"),
+        include_bytes!("igbinary/scalars.ser"),
+    ),
+    (
+        "objects",
+        include_bytes!("igbinary/objects.igb"),
+        include_bytes!("igbinary/objects.ser"),
+    ),
+    (
+        "references",
+        include_bytes!("igbinary/references.igb"),
+        include_bytes!("igbinary/references.ser"),
+    ),
+    (
+        "classes",
+        include_bytes!("igbinary/classes.igb"),
+        include_bytes!("igbinary/classes.ser"),
+    ),
+];
+
+#[test]
+fn golden_files_decode() {
+    for &(name, igb, ser) in GOLDEN {
+        let value = Value::parse_igbinary(igb).expect(name);
+        assert_eq!(value.to_bytes(), ser, "{}", name);
+    }
+}
+
+#[test]
+fn golden_files_encode() {
+    for &(name, igb, ser) in GOLDEN {
+        let value = Value::parse(ser).expect(name);
+        assert_eq!(value.to_igbinary().expect(name), igb, "{}", name);
+    }
+}
+
+#[test]
+fn wide_sizes_round_trip() {
+    let long = "x".repeat(300);
+    let mut entries: Vec<(ArrayKey<&str>, Value<&str>)> = (0..300)
+        .map(|i| (ArrayKey::Int(i), Value::Int(i64::MIN + i)))
+        .collect();
+    entries.push((ArrayKey::String(&long), Value::String(&long)));
+    let value = Value::Array(entries);
+
+    let igb = value.to_igbinary().expect("encodable");
+    assert_eq!(&igb[4..7], b"\x15\x01\x2d");
+    let decoded = Value::parse_igbinary(&igb[..]).expect("valid igbinary");
+    assert_eq!(decoded.to_bytes(), value.to_bytes());
+}
+
+#[test]
+fn malformed_input() {
+    for (input, expected) in &[
+        (&b""[..], Error::UnexpectedEof),
+        (b"\x00\x00\x00\x03\x00", Error::BadToken(4)),
+        (b"\x00\x00\x00\x02\x06", Error::UnexpectedEof),
+        (b"\x00\x00\x00\x02\xff", Error::BadToken(5)),
+        (b"\x00\x00\x00\x02\x00\x00", Error::TrailingData(5)),
+        (
+            b"\x00\x00\x00\x02\x14\x01\x06\x00\x01\x05",
+            Error::BadReference(5),
+        ),
+        (b"\x00\x00\x00\x02\x14\x01\x0e\x00\x00", Error::BadToken(8)),
+        (
+            b"\x00\x00\x00\x02\x14\x01\x0c\x00",
+            Error::BadArrayKeyType(7),
+        ),
+        (b"\x00\x00\x00\x02\x14\x10\x00", Error::UnexpectedEof),
+    ] {
+        match Value::parse_igbinary(*input) {
+            Err(IoError::Phpser(err)) => assert_eq!(
+                format!("{:?}", err),
+                format!("{:?}", expected),
+                "{:?}",
+                input
+            ),
+            result => panic!("{:?} parsed as {:?}", input, result),
+        }
+    }
+}
+
+#[test]
+fn unencodable_values() {
+    let enum_case = Value::parse("E:7:\"Foo:Bar\";").expect("valid enum");
+    let err = enum_case
+        .to_igbinary()
+        .expect_err("enums are not supported");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let dangling = Value::parse("a:1:{i:0;R:5;}").expect("valid syntax");
+    let err = dangling.to_igbinary().expect_err("unknown slot");
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn empty_strings() {
+    let igb = b"\x00\x00\x00\x02\x14\x01\x0d\x0d";
+    let decoded = Value::parse_igbinary(&igb[..]).expect("valid igbinary");
+    assert_eq!(decoded.to_bytes(), b"a:1:{s:0:\"\";s:0:\"\";}");
+    assert_eq!(decoded.to_igbinary().expect("encodable"), igb);
+}
@@ -0,0 +1,48 @@
+<?php
+// Regenerates the golden files in this directory from PHP.
+//
+// Run with `php -d extension=igbinary tests/igbinary/generate.php`.
+// The PHP and igbinary versions are written to `VERSION`,
+// which should be committed together with the regenerated files.
+
+class Foo
+{
+    protected $p = 1;
+    private $q = null;
+}
+
+class Bar implements Serializable
+{
+    public function serialize()
+    {
+        return "abc";
+    }
+
+    public function unserialize($data)
+    {
+    }
+}
+
+$x = new stdClass;
+$x->name = "x";
+$y = new stdClass;
+$y->name = "y";
+
+$references = [1];
+$references[1] = &$references[0];
+
+$values = [
+    "scalars" => [null, true, false, 1, -1, 300, -70000, 1099511627776, 1.5, "", "abc", "abc"],
+    "objects" => [$x, $x, $y],
+    "references" => $references,
+    "classes" => [new Foo, new Bar],
+];
+
+foreach ($values as $name => $value) {
+    file_put_contents(__DIR__ . "/$name.igb", igbinary_serialize($value));
+    file_put_contents(__DIR__ . "/$name.ser", serialize($value));
+}
+
+$version = "PHP " . PHP_VERSION . ", igbinary " . phpversion("igbinary") . "\n";
+file_put_contents(__DIR__ . "/VERSION", $version);
+echo $version;
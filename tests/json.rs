@@ -0,0 +1,354 @@
+use std::io;
+
+use phpser::*;
+
+fn json(ser: &str) -> String {
+    Value::parse(ser)
+        .expect("valid input")
+        .to_json()
+        .expect("encodable")
+}
+
+fn json_with(ser: &[u8], options: &JsonOptions) -> io::Result<String> {
+    Value::parse(ser)
+        .expect("valid input")
+        .to_json_with(options)
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(json("N;"), "null");
+    assert_eq!(json("b:1;"), "true");
+    assert_eq!(json("i:-42;"), "-42");
+    assert_eq!(json("d:0.1;"), "0.1");
+    assert_eq!(json("d:1;"), "1");
+    assert_eq!(json("d:1.0E+25;"), "1.0e+25");
+    assert_eq!(
+        json("s:9:\"a/\"b\\\n\u{1}\u{e9}\";"),
+        r#""a\/\"b\\\n\u0001\u00e9""#
+    );
+    assert_eq!(json("s:4:\"\u{1f600}\";"), r#""\ud83d\ude00""#);
+}
+
+#[test]
+fn flags() {
+    let options = JsonOptions {
+        unescaped_slashes: true,
+        unescaped_unicode: true,
+        preserve_zero_fraction: true,
+        ..JsonOptions::default()
+    };
+    assert_eq!(
+        json_with(
+            b"a:3:{i:0;d:1;i:1;s:3:\"/\xc3\xa9\";i:2;s:3:\"\xe2\x80\xa8\";}",
+            &options
+        )
+        .expect("encodable"),
+        "[1.0,\"/\u{e9}\",\"\\u2028\"]"
+    );
+}
+
+#[test]
+fn arrays_and_objects() {
+    assert_eq!(json("a:0:{}"), "[]");
+    assert_eq!(json("a:2:{i:0;i:1;i:1;i:2;}"), "[1,2]");
+    assert_eq!(json("a:2:{i:1;i:1;i:0;i:2;}"), r#"{"1":1,"0":2}"#);
+    assert_eq!(json("a:1:{s:1:\"k\";a:0:{}}"), r#"{"k":[]}"#);
+    assert_eq!(
+        json("O:3:\"Foo\":3:{s:1:\"a\";i:1;s:4:\"\0*\0b\";i:2;s:6:\"\0Foo\0c\";i:3;}"),
+        r#"{"a":1}"#
+    );
+    assert_eq!(json("O:8:\"stdClass\":0:{}"), "{}");
+    assert_eq!(json("C:3:\"Foo\":3:{abc}"), "{}");
+
+    let options = ParseOptions {
+        allowed_classes: ClassPolicy::None,
+        ..ParseOptions::default()
+    };
+    let incomplete = Value::parse_with("O:3:\"Foo\":1:{s:1:\"a\";N;}", &options)
+        .expect("valid input")
+        .to_json()
+        .expect("encodable");
+    assert_eq!(
+        incomplete,
+        r#"{"__PHP_Incomplete_Class_Name":"Foo","a":null}"#
+    );
+}
+
+#[test]
+fn pretty_print() {
+    let options = JsonOptions {
+        pretty_print: true,
+        ..JsonOptions::default()
+    };
+    assert_eq!(
+        json_with(b"a:2:{s:1:\"a\";a:1:{i:0;i:1;}s:1:\"b\";a:0:{}}", &options).expect("encodable"),
+        "{\n    \"a\": [\n        1\n    ],\n    \"b\": []\n}"
+    );
+}
+
+#[test]
+fn references_are_resolved() {
+    assert_eq!(
+        json("a:3:{i:0;O:8:\"stdClass\":1:{s:1:\"x\";i:1;}i:1;r:2;i:2;R:3;}"),
+        r#"[{"x":1},{"x":1},1]"#
+    );
+    let recursive = json_with(b"a:1:{i:0;R:1;}", &JsonOptions::default());
+    assert_eq!(
+        recursive.expect_err("recursive").kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
+
+#[test]
+fn errors() {
+    let default = JsonOptions::default();
+    for input in &[&b"d:NAN;"[..], b"E:7:\"Foo:Bar\";", b"a:1:{i:0;R:5;}"] {
+        let err = json_with(input, &default).expect_err("not encodable");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    let shallow = JsonOptions {
+        max_depth: 1,
+        ..JsonOptions::default()
+    };
+    assert!(json_with(b"a:1:{i:0;a:0:{}}", &shallow).is_err());
+    assert!(json_with(b"a:1:{i:0;i:0;}", &shallow).is_ok());
+}
+
+#[test]
+fn invalid_utf8() {
+    let input = b"s:4:\"a\xffbc\";";
+    let err = json_with(input, &JsonOptions::default()).expect_err("invalid UTF-8");
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let substitute = JsonOptions {
+        invalid_utf8: InvalidUtf8::Substitute,
+        ..JsonOptions::default()
+    };
+    assert_eq!(
+        json_with(input, &substitute).expect("substituted"),
+        r#""a\ufffdbc""#
+    );
+
+    let base64 = JsonOptions {
+        invalid_utf8: InvalidUtf8::Base64,
+        ..JsonOptions::default()
+    };
+    assert_eq!(json_with(input, &base64).expect("base64"), r#""Yf9iYw==""#);
+    let annotated = JsonOptions {
+        annotated: true,
+        ..base64
+    };
+    assert_eq!(
+        json_with(input, &annotated).expect("base64"),
+        r#"{"$base64":"Yf9iYw=="}"#
+    );
+}
+
+#[test]
+fn annotated() {
+    let options = JsonOptions {
+        annotated: true,
+        ..JsonOptions::default()
+    };
+    let annotated = |input: &str| json_with(input.as_bytes(), &options).expect("encodable");
+    assert_eq!(annotated("d:1;"), "1.0");
+    assert_eq!(annotated("d:-INF;"), r#"{"$float":"-INF"}"#);
+    assert_eq!(
+        annotated("a:2:{i:0;s:1:\"a\";s:2:\"1x\";N;}"),
+        r#"{"$array":[[0,"a"],["1x",null]]}"#
+    );
+    assert_eq!(
+        annotated("O:3:\"Foo\":3:{s:1:\"a\";i:1;s:4:\"\0*\0b\";i:2;s:6:\"\0Bar\0c\";r:1;}"),
+        concat!(
+            r#"{"$object":"Foo","properties":["#,
+            r#"{"name":"a","value":1},"#,
+            r#"{"name":"b","visibility":"protected","value":2},"#,
+            r#"{"name":"c","visibility":"private","class":"Bar","value":{"$objref":1}}]}"#,
+        )
+    );
+    assert_eq!(
+        annotated("a:2:{i:0;C:3:\"Foo\":3:{abc}i:1;E:7:\"Foo:Bar\";}"),
+        concat!(
+            r#"{"$array":[[0,{"$serializable":"Foo","data":"abc"}],"#,
+            r#"[1,{"$enum":"Foo","case":"Bar"}]]}"#,
+        )
+    );
+}
+
+fn decode(json: &str, assoc: bool) -> String {
+    let value = Value::from_json(json, assoc).expect("valid JSON");
+    String::from_utf8(value.to_bytes()).expect("UTF-8 output")
+}
+
+#[test]
+fn decode_values() {
+    assert_eq!(
+        decode(" [1, -0, 2.5, 1e2, \"a\", true, null] ", false),
+        "a:7:{i:0;i:1;i:1;i:0;i:2;d:2.5;i:3;d:100;i:4;s:1:\"a\";i:5;b:1;i:6;N;}"
+    );
+    assert_eq!(
+        decode(r#""\"\\\/\n\u00e9\ud83d\ude00""#, false),
+        "s:10:\"\"\\/\n\u{e9}\u{1f600}\";"
+    );
+}
+
+#[test]
+fn decode_objects() {
+    let json = r#"{"a": 1, "1": 2, "01": 3, "a": 4, "": {}}"#;
+    assert_eq!(
+        decode(json, false),
+        concat!(
+            "O:8:\"stdClass\":4:{s:1:\"a\";i:4;s:1:\"1\";i:2;s:2:\"01\";i:3;",
+            "s:0:\"\";O:8:\"stdClass\":0:{}}",
+        )
+    );
+    assert_eq!(
+        decode(json, true),
+        "a:4:{s:1:\"a\";i:4;i:1;i:2;s:2:\"01\";i:3;s:0:\"\";a:0:{}}"
+    );
+}
+
+#[test]
+fn decode_big_ints() {
+    assert_eq!(
+        decode(
+            "[9223372036854775807, -9223372036854775808, 9223372036854775808]",
+            false
+        ),
+        "a:3:{i:0;i:9223372036854775807;i:1;i:-9223372036854775808;i:2;d:9.223372036854776E+18;}"
+    );
+    let options = JsonDecodeOptions {
+        bigint_as_string: true,
+        ..JsonDecodeOptions::default()
+    };
+    let value = Value::from_json_with("-9223372036854775809", &options).expect("valid JSON");
+    assert_eq!(value.to_bytes(), b"s:20:\"-9223372036854775809\";");
+}
+
+#[test]
+fn decode_errors() {
+    for (json, expected) in &[
+        ("", Error::UnexpectedEof),
+        ("tru", Error::UnexpectedEof),
+        ("[1,]", Error::BadToken(4)),
+        ("{1:2}", Error::BadToken(2)),
+        ("01", Error::TrailingData(1)),
+        ("1.e", Error::BadNumber(3)),
+        ("\"\u{1}\"", Error::BadToken(2)),
+        ("\"\\ud800\"", Error::BadToken(2)),
+        ("\"\\udc00\"", Error::BadToken(2)),
+        ("{\"\\u0000a\":1}", Error::BadToken(2)),
+    ] {
+        match Value::from_json(json, false) {
+            Err(err) => assert_eq!(
+                format!("{:?}", err),
+                format!("{:?}", expected),
+                "{:?}",
+                json
+            ),
+            Ok(value) => panic!("{:?} decoded as {:?}", json, value),
+        }
+    }
+    assert!(Value::from_json("{\"\\u0000a\":1}", true).is_ok());
+
+    let shallow = JsonDecodeOptions {
+        max_depth: 1,
+        ..JsonDecodeOptions::default()
+    };
+    assert!(Value::from_json_with("[1]", &shallow).is_ok());
+    assert!(matches!(
+        Value::from_json_with("[[1]]", &shallow),
+        Err(Error::DepthLimitExceeded(2))
+    ));
+}
+
+#[test]
+fn annotated_float_precision() {
+    let value = Value::<&str>::Float(0.1 + 0.2);
+    let plain = JsonOptions {
+        float_precision: FloatPrecision::Digits(3),
+        ..JsonOptions::default()
+    };
+    assert_eq!(value.to_json_with(&plain).expect("encodable"), "0.3");
+
+    let annotated = JsonOptions {
+        annotated: true,
+        ..plain
+    };
+    let json = value.to_json_with(&annotated).expect("encodable");
+    assert_eq!(json, "0.30000000000000004");
+    match Value::from_annotated_json(&json) {
+        Ok(Value::Float(float)) => assert_eq!(float, 0.1 + 0.2),
+        result => panic!("{} decoded as {:?}", json, result),
+    }
+}
+
+#[test]
+fn annotated_round_trip() {
+    let options = JsonOptions {
+        annotated: true,
+        invalid_utf8: InvalidUtf8::Base64,
+        pretty_print: true,
+        ..JsonOptions::default()
+    };
+    for input in &[
+        &b"N;"[..],
+        b"d:0.1;",
+        b"d:1;",
+        b"d:NAN;",
+        b"s:3:\"\xff\x00a\";",
+        b"a:2:{i:5;s:1:\"a\";s:2:\"1x\";a:0:{}}",
+        b"a:1:{s:2:\"\xc3\x28\";i:1;}",
+        b"O:3:\"Foo\":3:{s:1:\"a\";i:1;s:4:\"\0*\0b\";i:2;s:6:\"\0Bar\0c\";r:1;}",
+        b"a:3:{i:0;C:3:\"Foo\":3:{abc}i:1;E:7:\"Foo:Bar\";i:2;R:2;}",
+    ] {
+        let value = Value::parse(*input).expect("valid input");
+        let json = value.to_json_with(&options).expect("encodable");
+        let decoded = Value::from_annotated_json(&json).expect("annotated JSON");
+        assert_eq!(decoded.to_bytes(), *input, "{}", json);
+    }
+
+    let incomplete = Value::parse_with(
+        "O:3:\"Foo\":0:{}",
+        &ParseOptions {
+            allowed_classes: ClassPolicy::None,
+            ..ParseOptions::default()
+        },
+    )
+    .expect("valid input");
+    let json = incomplete.to_json_with(&options).expect("encodable");
+    let decoded = Value::from_annotated_json(&json).expect("annotated JSON");
+    assert_eq!(decoded.to_bytes(), incomplete.to_bytes());
+}
+
+#[test]
+fn annotated_errors() {
+    for json in &[
+        "[1]",
+        r#"{"a":1}"#,
+        r#"{"$float":"1"}"#,
+        r#"{"$array":[[1]]}"#,
+        r#"{"$array":[[1.5,2]]}"#,
+        r#"{"$object":"Foo"}"#,
+        r#"{"$ref":-1}"#,
+        r#"{"$float":"NAN","x":1}"#,
+        r#"{"$ref":1,"$array":[]}"#,
+        r#"{"$enum":"Foo","case":"Bar","data":"x"}"#,
+        r#"{"$object":"Foo","properties":[{"name":"a","value":1,"class":"Foo"}]}"#,
+    ] {
+        assert!(
+            matches!(Value::from_annotated_json(json), Err(Error::BadToken(_))),
+            "{}",
+            json
+        );
+    }
+    assert!(matches!(
+        Value::from_annotated_json(r#"{"$base64":"a"}"#),
+        Err(Error::BadToken(1))
+    ));
+    assert!(matches!(
+        Value::from_annotated_json(r#"{"$float":"NAN", "x":1}"#),
+        Err(Error::BadToken(18))
+    ));
+}
@@ -0,0 +1,161 @@
+use std::thread;
+
+use phpser::*;
+
+fn parse_with(input: &str, options: &ParseOptions) -> Result<Vec<u8>, Error> {
+    match Value::parse_with(input, options) {
+        Ok(value) => Ok(value.to_bytes()),
+        Err(IoError::Phpser(err)) => Err(err),
+        Err(IoError::Io(err)) => panic!("unexpected I/O error {}", err),
+    }
+}
+
+fn nested(depth: usize) -> String {
+    let mut input = "a:1:{i:0;".repeat(depth);
+    input.push_str("N;");
+    input.push_str(&"}".repeat(depth));
+    input
+}
+
+#[test]
+fn max_depth() {
+    let options = ParseOptions {
+        max_depth: 2,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with(&nested(2), &options).is_ok());
+    assert!(matches!(
+        parse_with(&nested(3), &options),
+        Err(Error::DepthLimitExceeded(22))
+    ));
+    assert!(matches!(
+        parse_with("a:1:{i:0;O:8:\"stdClass\":1:{s:1:\"a\";a:0:{}}}", &options),
+        Err(Error::DepthLimitExceeded(_))
+    ));
+}
+
+#[test]
+fn deep_nesting_does_not_overflow() {
+    let input = nested(1_000_000);
+
+    let err = parse_with(&input, &ParseOptions::default()).expect_err("too deep");
+    assert!(matches!(err, Error::DepthLimitExceeded(_)));
+
+    let unlimited = ParseOptions {
+        max_depth: usize::MAX,
+        ..ParseOptions::default()
+    };
+    let events = EventParser::with_options(Cursor::new(input.as_str()), &unlimited)
+        .collect::<IoResult<Vec<_>>>()
+        .expect("valid input");
+    assert_eq!(events.len(), 3_000_001);
+}
+
+#[test]
+fn max_depth_default() {
+    // dropping a `Value` recurses once per level
+    thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(|| {
+            let defaults = ParseOptions::default();
+            assert!(parse_with(&nested(4096), &defaults).is_ok());
+            assert!(matches!(
+                parse_with(&nested(4097), &defaults),
+                Err(Error::DepthLimitExceeded(_))
+            ));
+        })
+        .expect("spawn thread")
+        .join()
+        .expect("no panic");
+}
+
+#[test]
+fn max_elements() {
+    let options = ParseOptions {
+        max_elements: 3,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with("a:2:{i:0;a:1:{i:0;N;}i:1;N;}", &options).is_ok());
+    assert!(matches!(
+        parse_with("a:2:{i:0;a:2:{i:0;N;i:1;N;}i:1;N;}", &options),
+        Err(Error::ElementLimitExceeded(13))
+    ));
+    assert!(matches!(
+        parse_with("O:8:\"stdClass\":4:{}", &options),
+        Err(Error::ElementLimitExceeded(_))
+    ));
+}
+
+#[test]
+fn max_string_len() {
+    let options = ParseOptions {
+        max_string_len: 3,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with("s:3:\"abc\";", &options).is_ok());
+    for input in &[
+        "s:4:\"abcd\";",
+        "a:1:{s:4:\"abcd\";N;}",
+        "O:4:\"Abcd\":0:{}",
+        "C:3:\"Foo\":4:{abcd}",
+        "E:5:\"A:Bcd\";",
+    ] {
+        assert!(
+            matches!(
+                parse_with(input, &options),
+                Err(Error::StringLimitExceeded(_))
+            ),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn max_allocation() {
+    let options = ParseOptions {
+        max_allocation: 8,
+        ..ParseOptions::default()
+    };
+    assert!(parse_with("a:2:{i:0;s:4:\"abcd\";i:1;s:4:\"efgh\";}", &options).is_err());
+    assert!(parse_with("s:8:\"abcdefgh\";", &options).is_ok());
+    assert!(matches!(
+        parse_with("s:9:\"abcdefghi\";", &options),
+        Err(Error::AllocationLimitExceeded(_))
+    ));
+    assert!(matches!(
+        parse_with("a:1:{i:0;N;}", &options),
+        Err(Error::AllocationLimitExceeded(_))
+    ));
+}
+
+#[test]
+fn limits_apply_to_every_entry_point() {
+    let options = ParseOptions {
+        max_depth: 1,
+        ..ParseOptions::default()
+    };
+    let input = nested(2);
+    let is_depth_err = |result: IoResult<Value<Vec<u8>>>| {
+        matches!(result, Err(IoError::Phpser(Error::DepthLimitExceeded(_))))
+    };
+    assert!(is_depth_err(Value::from_source_with(
+        ByteReader::new(input.as_bytes(), input.len()),
+        &options
+    )));
+    assert!(is_depth_err(Value::parse_exact_with(
+        input.as_bytes().to_vec(),
+        &options
+    )));
+    assert!(matches!(
+        Values::with_options(Cursor::new(input.as_str()), &options).next(),
+        Some(Err(IoError::Phpser(Error::DepthLimitExceeded(_))))
+    ));
+
+    let mut parser = PushParser::<Vec<u8>>::with_options(&options);
+    parser.feed(input.as_bytes());
+    assert!(matches!(
+        parser.read_value(),
+        Err(Error::DepthLimitExceeded(_))
+    ));
+}
@@ -0,0 +1,207 @@
+use phpser::*;
+
+fn float(input: &str) -> f64 {
+    match Value::parse(input) {
+        Ok(Value::Float(float)) => float,
+        result => panic!("{:?} parsed as {:?}", input, result),
+    }
+}
+
+#[test]
+fn float_spellings() {
+    assert_eq!(float("d:INF;"), f64::INFINITY);
+    assert_eq!(float("d:-INF;"), f64::NEG_INFINITY);
+    assert!(float("d:NAN;").is_nan());
+    for &(input, expected) in &[
+        ("d:0;", 0.0),
+        ("d:-0;", -0.0),
+        ("d:+1;", 1.0),
+        ("d:1.5;", 1.5),
+        ("d:.5;", 0.5),
+        ("d:5.;", 5.0),
+        ("d:-0.25;", -0.25),
+        ("d:1.0E+25;", 1e25),
+        ("d:1.0E25;", 1e25),
+        ("d:1e-5;", 1e-5),
+        ("d:2E+2;", 200.0),
+        ("d:1.7976931348623157E+308;", f64::MAX),
+    ] {
+        assert_eq!(float(input), expected, "{}", input);
+    }
+    assert!(float("d:-0;").is_sign_negative());
+}
+
+#[test]
+fn bad_float_spellings() {
+    for input in &[
+        "d:;",
+        "d:.;",
+        "d:-;",
+        "d:inf;",
+        "d:nan;",
+        "d:+INF;",
+        "d:Infinity;",
+        "d:1e;",
+        "d:1E+;",
+        "d:1.5.;",
+        "d: 1;",
+        "d:0x10;",
+    ] {
+        match Value::parse(*input) {
+            Err(IoError::Phpser(Error::BadNumber(offset))) => {
+                assert_eq!(offset, input.len(), "{}", input)
+            }
+            result => panic!("{:?} parsed as {:?}", input, result),
+        }
+    }
+}
+
+#[test]
+fn enum_cases() {
+    let input = "E:18:\"App\\Status:Pending\";";
+    match Value::parse(input) {
+        Ok(Value::Enum(case)) => {
+            assert_eq!(*case.class(), "App\\Status");
+            assert_eq!(*case.case(), "Pending");
+            assert_eq!(Value::Enum(case).to_bytes(), input.as_bytes());
+        }
+        result => panic!("parsed as {:?}", result),
+    }
+
+    let nested = "O:3:\"Foo\":1:{s:6:\"status\";E:7:\"Foo:Bar\";}";
+    assert_eq!(
+        Value::parse(nested).expect("valid input").to_bytes(),
+        nested.as_bytes()
+    );
+
+    let constructed = Value::Enum(EnumCase::new(&b"A"[..], b"B"));
+    assert_eq!(constructed.to_bytes(), b"E:3:\"A:B\";");
+}
+
+#[test]
+fn bad_enum_names() {
+    for input in &[
+        "E:3:\"Foo\";",
+        "E:4:\":Foo\";",
+        "E:4:\"Foo:\";",
+        "E:1:\":\";",
+    ] {
+        match Value::parse(*input) {
+            Err(IoError::Phpser(Error::BadEnumName(offset))) => {
+                assert_eq!(offset, input.len(), "{}", input)
+            }
+            result => panic!("{:?} parsed as {:?}", input, result),
+        }
+    }
+    assert!(matches!(
+        Value::parse("E:3:\"A:B\""),
+        Err(IoError::Phpser(Error::UnexpectedEof))
+    ));
+}
+
+fn refs(input: &str) -> Vec<(RefKind, usize)> {
+    match Value::parse(input) {
+        Ok(Value::Array(entries)) => entries
+            .into_iter()
+            .filter_map(|(_, value)| match value {
+                Value::Reference(r#ref) => Some((r#ref.kind(), r#ref.index())),
+                _ => None,
+            })
+            .collect(),
+        result => panic!("{:?} parsed as {:?}", input, result),
+    }
+}
+
+#[test]
+fn object_and_value_references() {
+    let input = "a:3:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;i:2;R:2;}";
+    assert_eq!(refs(input), vec![(RefKind::Object, 2), (RefKind::Value, 2)]);
+    assert_eq!(
+        Value::parse(input).expect("valid input").to_bytes(),
+        input.as_bytes()
+    );
+
+    assert_eq!(Ref::new(3).kind(), RefKind::Value);
+    assert_eq!(Ref::new_object(3).kind(), RefKind::Object);
+    let constructed: Value<&str> = Value::Array(vec![
+        (ArrayKey::Int(0), Value::Reference(Ref::new_object(1))),
+        (
+            ArrayKey::Int(1),
+            Value::Reference(Ref::with_kind(RefKind::Value, 1)),
+        ),
+    ]);
+    assert_eq!(constructed.to_bytes(), b"a:2:{i:0;r:1;i:1;R:1;}");
+
+    assert!(matches!(
+        Value::parse("r:x;"),
+        Err(IoError::Phpser(Error::BadNumber(_)))
+    ));
+}
+
+#[test]
+fn reference_slots() {
+    // `r:` takes slot 3, so `R:3` points to the `r:` entry, which is the object
+    let graph = Value::parse("a:3:{i:0;O:8:\"stdClass\":0:{}i:1;r:2;i:2;R:3;}")
+        .expect("valid input")
+        .resolve()
+        .expect("valid references");
+    let targets = match graph.get(graph.root()) {
+        Some(Node::Array(entries)) => entries
+            .iter()
+            .map(|(_, edge)| edge.target())
+            .collect::<Vec<_>>(),
+        node => panic!("root is {:?}", node),
+    };
+    assert_eq!(targets.first(), targets.get(1));
+    assert_eq!(targets.get(1), targets.get(2));
+
+    // `R:` does not take a slot, so slot 3 does not exist
+    let dangling = Value::parse("a:2:{i:0;R:1;i:1;R:3;}").expect("valid input");
+    assert!(matches!(dangling.resolve(), Err(Error::BadReference(3))));
+}
+
+#[test]
+fn parse_prefix() {
+    let (value, rest) = Value::parse_prefix("i:1;s:1:\"a\";tail").expect("valid input");
+    assert!(matches!(value, Value::Int(1)));
+    assert_eq!(rest, "s:1:\"a\";tail");
+    let (value, rest) = Value::parse_prefix(rest).expect("valid input");
+    assert!(matches!(value, Value::String("a")));
+    assert_eq!(rest, "tail");
+
+    let (value, rest) = Value::parse_prefix(&b"a:0:{}"[..]).expect("valid input");
+    assert!(matches!(value, Value::Array(entries) if entries.is_empty()));
+    assert!(rest.is_empty());
+
+    assert!(matches!(
+        Value::parse_prefix("i:1"),
+        Err(IoError::Phpser(Error::UnexpectedEof))
+    ));
+}
+
+#[test]
+fn parse_exact() {
+    assert!(matches!(Value::parse_exact("i:1;"), Ok(Value::Int(1))));
+    assert!(matches!(
+        Value::parse_exact("a:1:{i:0;N;}"),
+        Ok(Value::Array(_))
+    ));
+    for (input, offset) in &[
+        ("i:1;i:2;", 4),
+        ("N; ", 2),
+        ("a:0:{}\n", 6),
+        ("s:1:\"a\";\0", 8),
+    ] {
+        match Value::parse_exact(*input) {
+            Err(IoError::Phpser(Error::TrailingData(actual))) => {
+                assert_eq!(actual, *offset, "{:?}", input)
+            }
+            result => panic!("{:?} parsed as {:?}", input, result),
+        }
+    }
+    assert!(Value::parse("i:1;i:2;").is_ok());
+    assert!(matches!(
+        Value::parse_exact(""),
+        Err(IoError::Phpser(Error::UnexpectedEof))
+    ));
+}
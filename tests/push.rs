@@ -0,0 +1,139 @@
+use phpser::*;
+
+#[test]
+fn huge_declared_length_does_not_allocate() {
+    for (header, entry) in &[
+        (&b"a:9999999999:{"[..], &b"i:0;N;"[..]),
+        (b"O:8:\"stdClass\":9999999999:{", b"s:1:\"a\";N;"),
+    ] {
+        let mut parser = PushParser::<Vec<u8>>::new();
+        parser.feed(header);
+        assert!(matches!(parser.read_value(), Ok(Progress::NeedMoreData)));
+        parser.feed(entry);
+        assert!(matches!(parser.read_value(), Ok(Progress::NeedMoreData)));
+    }
+}
+
+#[test]
+fn huge_declared_length_exceeds_limit() {
+    let mut parser = PushParser::<Vec<u8>>::with_limit(20, &ParseOptions::default());
+    parser.feed(b"a:9999999999:{");
+    assert!(matches!(parser.read_value(), Ok(Progress::NeedMoreData)));
+    parser.feed(b"i:0;N;");
+    assert!(matches!(parser.read_value(), Err(Error::UnexpectedEof)));
+}
+
+const INPUTS: &[&[u8]] = &[
+    b"N;",
+    b"i:-123;",
+    b"d:1.5E+25;",
+    b"s:5:\"a;b}\xc3\";",
+    b"a:2:{i:0;s:1:\"x\";s:1:\"k\";a:1:{i:0;R:2;}}",
+    b"O:3:\"Foo\":2:{s:4:\"\0*\0a\";b:1;s:6:\"\0Foo\0b\";r:1;}",
+    b"a:2:{i:0;C:3:\"Bar\":4:{a}b;}i:1;E:7:\"Foo:Bar\";}",
+];
+
+fn read_value(parser: &mut PushParser) -> Option<Value<Vec<u8>>> {
+    match parser.read_value() {
+        Ok(Progress::Ready(value)) => Some(value),
+        Ok(Progress::NeedMoreData) => None,
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+fn split_at_every_byte() {
+    for &input in INPUTS {
+        for split in 0..=input.len() {
+            let (first, second) = input.split_at(split);
+            let mut parser = PushParser::new();
+            parser.feed(first);
+            let value = match read_value(&mut parser) {
+                Some(value) => {
+                    assert_eq!(split, input.len());
+                    value
+                }
+                None => {
+                    parser.feed(second);
+                    read_value(&mut parser).expect("complete input")
+                }
+            };
+            assert_eq!(value.to_bytes(), input, "split at {}", split);
+            assert!(parser.is_finished());
+            assert!(parser.remaining().is_empty());
+            assert_eq!(parser.offset(), input.len());
+            assert!(matches!(parser.read_value(), Ok(Progress::Finished)));
+        }
+    }
+}
+
+#[test]
+fn events_byte_by_byte() {
+    for &input in INPUTS {
+        let expected: Vec<_> = EventParser::new(Cursor::new(input))
+            .map(|event| {
+                let event = event.expect("valid input");
+                (event.offset(), format!("{:?}", event.kind()))
+            })
+            .collect();
+
+        let mut parser = PushParser::<Vec<u8>>::new();
+        let mut events = vec![];
+        for byte in input.chunks(1) {
+            parser.feed(byte);
+            while let Progress::Ready(event) = parser.next_event().expect("valid input") {
+                events.push((event.offset(), format!("{:?}", event.kind())));
+            }
+        }
+        assert_eq!(events, expected, "{:?}", String::from_utf8_lossy(input));
+        assert!(parser.is_finished());
+    }
+}
+
+#[test]
+fn split_utf8_strings() {
+    let input = "a:1:{s:2:\"\u{e9}\";s:3:\"\u{20ac}\";}".as_bytes();
+    for split in 0..input.len() {
+        let mut parser = PushParser::<String>::new();
+        parser.feed(&input[..split]);
+        assert!(matches!(parser.read_value(), Ok(Progress::NeedMoreData)));
+        parser.feed(&input[split..]);
+        match parser.read_value() {
+            Ok(Progress::Ready(value)) => assert_eq!(value.to_bytes(), input),
+            result => panic!("split at {}: {:?}", split, result),
+        }
+    }
+}
+
+#[test]
+fn truncated_input() {
+    let input = INPUTS.last().copied().unwrap_or_default();
+    for split in 0..input.len() {
+        let mut parser = PushParser::<Vec<u8>>::new();
+        parser.feed(&input[..split]);
+        assert!(read_value(&mut parser).is_none());
+        assert!(!parser.is_finished());
+    }
+
+    let mut parser = PushParser::<Vec<u8>>::with_limit(6, &ParseOptions::default());
+    parser.feed(b"i:12");
+    assert!(matches!(parser.read_value(), Ok(Progress::NeedMoreData)));
+    parser.feed(b"34");
+    assert!(matches!(parser.read_value(), Err(Error::UnexpectedEof)));
+}
+
+#[test]
+fn consecutive_values() {
+    let mut parser = PushParser::<Vec<u8>>::new();
+    parser.feed(b"i:1;a:1:{i:0;N");
+    assert!(matches!(read_value(&mut parser), Some(Value::Int(1))));
+    assert_eq!(parser.remaining(), b"a:1:{i:0;N");
+    parser.restart();
+    assert!(read_value(&mut parser).is_none());
+    parser.feed(b";}x:");
+    let value = read_value(&mut parser).expect("complete input");
+    assert_eq!(value.to_bytes(), b"a:1:{i:0;N;}");
+    assert_eq!(parser.offset(), 16);
+    parser.restart();
+    assert!(matches!(parser.read_value(), Err(Error::BadToken(17))));
+}
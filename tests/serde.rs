@@ -0,0 +1,363 @@
+#![cfg(feature = "serde")]
+
+use phpser::*;
+use serde_json::json;
+
+#[test]
+fn any_array_shapes() {
+    let list: serde_json::Value = from_str("a:2:{i:0;i:1;i:1;i:2;}").expect("valid input");
+    assert_eq!(list, json!([1, 2]));
+
+    let empty: serde_json::Value = from_str("a:0:{}").expect("valid input");
+    assert_eq!(empty, json!([]));
+
+    let nested: serde_json::Value =
+        from_str("a:2:{i:0;a:1:{i:0;s:1:\"a\";}i:1;a:1:{i:1;N;}}").expect("valid input");
+    assert_eq!(nested, json!([["a"], {"1": null}]));
+
+    let map: serde_json::Value =
+        from_str("a:3:{i:0;s:1:\"a\";i:1;a:1:{i:0;b:1;}i:5;d:0.5;}").expect("valid input");
+    assert_eq!(map, json!({"0": "a", "1": [true], "5": 0.5}));
+
+    let unordered: serde_json::Value =
+        from_str("a:3:{i:1;i:1;i:0;i:2;s:1:\"k\";i:3;}").expect("valid input");
+    assert_eq!(unordered, json!({"1": 1, "0": 2, "k": 3}));
+}
+
+#[test]
+fn map_from_list() {
+    let map: std::collections::BTreeMap<i64, i64> =
+        from_str("a:2:{i:0;i:1;i:1;i:2;}").expect("valid input");
+    assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+}
+
+#[test]
+fn incomplete_class() {
+    let input = "O:3:\"Foo\":1:{s:1:\"a\";a:1:{i:0;O:3:\"Bar\":0:{}}}";
+    let options = ParseOptions {
+        allowed_classes: ClassPolicy::None,
+        ..ParseOptions::default()
+    };
+    let value: serde_json::Value = from_str_with(input, &options).expect("valid input");
+    assert_eq!(
+        value,
+        json!({
+            "__PHP_Incomplete_Class_Name": "Foo",
+            "a": [{"__PHP_Incomplete_Class_Name": "Bar"}],
+        })
+    );
+
+    let allowed: serde_json::Value = from_str(input).expect("valid input");
+    assert_eq!(allowed, json!({"a": [{}]}));
+
+    let reject = ParseOptions {
+        disallowed_classes: DisallowedClassAction::Reject,
+        ..options
+    };
+    assert!(matches!(
+        from_str_with::<serde_json::Value>(input, &reject),
+        Err(SerdeError::Phpser(Error::DisallowedClass(0)))
+    ));
+}
+
+#[test]
+fn compound_headers() {
+    #[derive(serde::Serialize)]
+    struct Inner {
+        b: i32,
+    }
+    #[derive(serde::Serialize)]
+    struct Outer {
+        a: Vec<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        skipped: Option<i32>,
+        #[serde(flatten)]
+        inner: Inner,
+    }
+    let value = Outer {
+        a: vec![1, 2],
+        skipped: None,
+        inner: Inner { b: 3 },
+    };
+    assert_eq!(
+        to_string(&value).expect("serializable"),
+        "a:2:{s:1:\"a\";a:2:{i:0;i:1;i:1;i:2;}s:1:\"b\";i:3;}"
+    );
+
+    let unknown_len = (0..3).filter(|i| i % 2 == 0);
+    let seq = json!([{"x": [null]}, [1]]);
+    assert_eq!(
+        to_string(&seq).expect("serializable"),
+        "a:2:{i:0;a:1:{s:1:\"x\";a:1:{i:0;N;}}i:1;a:1:{i:0;i:1;}}"
+    );
+    struct Unsized<I>(std::cell::RefCell<Option<I>>);
+    impl<I: Iterator<Item = i32>> serde::Serialize for Unsized<I> {
+        fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            let iter = self.0.borrow_mut().take().expect("serialized once");
+            ser.collect_seq(iter)
+        }
+    }
+    let nested = vec![Unsized(std::cell::RefCell::new(Some(unknown_len)))];
+    assert_eq!(
+        to_string(&nested).expect("serializable"),
+        "a:1:{i:0;a:2:{i:0;i:0;i:1;i:2;}}"
+    );
+}
+
+#[test]
+fn wrong_declared_length() {
+    struct Liar;
+    impl serde::Serialize for Liar {
+        fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+            let mut seq = ser.serialize_seq(Some(2))?;
+            seq.serialize_element(&1)?;
+            seq.end()
+        }
+    }
+    assert!(to_vec(&Liar).is_err());
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct Record<'a> {
+    name: &'a str,
+    #[serde(with = "serde_bytes_compat")]
+    raw: Vec<u8>,
+    tags: Vec<String>,
+    parent: Option<Box<Record<'a>>>,
+    kind: Kind,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+enum Kind {
+    Leaf,
+    Branch(u8),
+}
+
+/// Serializes `Vec<u8>` as a byte string instead of a sequence.
+mod serde_bytes_compat {
+    pub fn serialize<S: serde::Serializer>(bytes: &[u8], ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(de: D) -> Result<Vec<u8>, D::Error> {
+        struct BytesVisitor;
+        impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a byte string")
+            }
+
+            fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(bytes.to_vec())
+            }
+        }
+        de.deserialize_bytes(BytesVisitor)
+    }
+}
+
+#[test]
+fn deserialize_struct_from_array_and_object() {
+    let input = concat!(
+        "a:5:{s:4:\"name\";s:4:\"root\";s:3:\"raw\";s:2:\"\\x\";",
+        "s:4:\"tags\";a:2:{i:0;s:1:\"a\";i:1;s:1:\"b\";}",
+        "s:6:\"parent\";O:6:\"Record\":5:{s:4:\"name\";s:5:\"child\";s:3:\"raw\";s:0:\"\";",
+        "s:4:\"tags\";a:0:{}s:6:\"parent\";N;s:4:\"kind\";E:9:\"Kind:Leaf\";}",
+        "s:4:\"kind\";a:1:{s:6:\"Branch\";i:3;}}",
+    );
+    let record: Record<'_> = from_str(input).expect("valid input");
+    assert_eq!(
+        record,
+        Record {
+            name: "root",
+            raw: b"\\x".to_vec(),
+            tags: vec!["a".to_string(), "b".to_string()],
+            parent: Some(Box::new(Record {
+                name: "child",
+                raw: vec![],
+                tags: vec![],
+                parent: None,
+                kind: Kind::Leaf,
+            })),
+            kind: Kind::Branch(3),
+        }
+    );
+
+    let name_offset = input.find("root").expect("name in input");
+    assert!(std::ptr::eq(
+        record.name.as_ptr(),
+        input[name_offset..].as_ptr()
+    ));
+}
+
+#[test]
+fn deserialize_borrowed_bytes() {
+    let input = b"a:2:{i:0;s:2:\"\xff\xfe\";i:1;s:1:\"a\";}";
+    let bytes: Vec<&[u8]> = from_bytes(input).expect("valid input");
+    assert_eq!(bytes, vec![&b"\xff\xfe"[..], b"a"]);
+
+    let owned: Vec<String> =
+        from_source(StringReader::new(&b"a:1:{i:0;s:1:\"a\";}"[..], 64)).expect("valid input");
+    assert_eq!(owned, vec!["a"]);
+}
+
+#[test]
+fn deserialize_seq_and_map() {
+    let list: Vec<i32> = from_str("a:2:{i:0;i:5;i:1;i:6;}").expect("valid input");
+    assert_eq!(list, vec![5, 6]);
+    assert!(from_str::<Vec<i32>>("a:2:{i:1;i:5;i:0;i:6;}").is_err());
+    assert!(from_str::<Vec<i32>>("a:1:{s:1:\"0\";i:5;}").is_err());
+
+    let map: std::collections::BTreeMap<String, Option<i32>> =
+        from_str("a:2:{s:1:\"a\";N;i:7;i:1;}").expect("valid input");
+    assert_eq!(
+        map.into_iter().collect::<Vec<_>>(),
+        vec![("7".to_string(), Some(1)), ("a".to_string(), None)]
+    );
+
+    let tuple: (bool, f64, String) =
+        from_str("a:3:{i:0;b:1;i:1;d:0.5;i:2;i:42;}").expect("valid input");
+    assert_eq!(tuple, (true, 0.5, "42".to_string()));
+}
+
+#[test]
+fn deserialize_ignored_fields() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Small {
+        b: i32,
+    }
+    let input = concat!(
+        "a:3:{s:1:\"a\";a:2:{i:0;O:3:\"Foo\":1:{s:1:\"x\";C:3:\"Bar\":2:{xy}}i:1;R:2;}",
+        "s:1:\"b\";i:5;s:1:\"c\";E:7:\"Foo:Bar\";}",
+    );
+    assert_eq!(
+        from_str::<Small>(input).expect("valid input"),
+        Small { b: 5 }
+    );
+
+    let shallow = ParseOptions {
+        max_depth: 2,
+        ..ParseOptions::default()
+    };
+    assert!(matches!(
+        from_str_with::<Small>(input, &shallow),
+        Err(SerdeError::Phpser(Error::DepthLimitExceeded(_)))
+    ));
+    assert!(from_str::<Small>("a:2:{s:1:\"a\";a:1:{i:0;x}s:1:\"b\";i:5;}").is_err());
+}
+
+#[test]
+fn deserialize_errors() {
+    assert!(from_str::<i32>("s:1:\"a\";").is_err());
+    assert!(from_str::<Vec<i32>>("a:2:{i:0;i:1;}").is_err());
+    assert!(from_str::<serde_json::Value>("a:1:{i:0;R:1;}").is_err());
+    assert!(from_str::<serde_json::Value>("C:3:\"Foo\":0:{}").is_err());
+    assert!(from_str::<Kind>("s:7:\"Unknown\";").is_err());
+}
+
+fn to_string_with<T: serde::Serialize>(value: &T, options: SerializerOptions) -> String {
+    let mut ser = Serializer::new(options);
+    value.serialize(&mut ser).expect("serializable");
+    String::from_utf8(ser.into_inner()).expect("UTF-8 output")
+}
+
+fn sample() -> Record<'static> {
+    Record {
+        name: "root",
+        raw: b"a\0b".to_vec(),
+        tags: vec!["a".to_string()],
+        parent: Some(Box::new(Record {
+            name: "child",
+            raw: vec![],
+            tags: vec![],
+            parent: None,
+            kind: Kind::Leaf,
+        })),
+        kind: Kind::Branch(3),
+    }
+}
+
+#[test]
+fn serialize_structs_and_variants() {
+    assert_eq!(
+        to_string(&sample()).expect("serializable"),
+        concat!(
+            "a:5:{s:4:\"name\";s:4:\"root\";s:3:\"raw\";s:3:\"a\0b\";",
+            "s:4:\"tags\";a:1:{i:0;s:1:\"a\";}",
+            "s:6:\"parent\";a:5:{s:4:\"name\";s:5:\"child\";s:3:\"raw\";s:0:\"\";",
+            "s:4:\"tags\";a:0:{}s:6:\"parent\";N;s:4:\"kind\";s:4:\"Leaf\";}",
+            "s:4:\"kind\";a:1:{s:6:\"Branch\";i:3;}}",
+        )
+    );
+
+    let objects = to_string_with(
+        &(Kind::Leaf, sample().parent),
+        SerializerOptions {
+            structs: StructStyle::Object(ClassName::Namespace("App\\".to_string())),
+            unit_variants: UnitVariantStyle::Enum(ClassName::Custom(|name| {
+                format!("App\\Enums\\{}", name)
+            })),
+            ..SerializerOptions::default()
+        },
+    );
+    assert_eq!(
+        objects,
+        concat!(
+            "a:2:{i:0;E:19:\"App\\Enums\\Kind:Leaf\";",
+            "i:1;O:10:\"App\\Record\":5:{s:4:\"name\";s:5:\"child\";s:3:\"raw\";s:0:\"\";",
+            "s:4:\"tags\";a:0:{}s:6:\"parent\";N;s:4:\"kind\";E:19:\"App\\Enums\\Kind:Leaf\";}}",
+        )
+    );
+}
+
+#[test]
+fn serialize_map_keys() {
+    let mut map = std::collections::BTreeMap::new();
+    map.insert("10", 'x');
+    map.insert("-1", 'y');
+    map.insert("01", 'z');
+    assert_eq!(
+        to_string(&map).expect("serializable"),
+        "a:3:{i:-1;s:1:\"y\";s:2:\"01\";s:1:\"z\";i:10;s:1:\"x\";}"
+    );
+
+    let mut bad = std::collections::BTreeMap::new();
+    bad.insert(vec![1], 1);
+    assert!(to_vec(&bad).is_err());
+    assert!(to_vec(&u64::MAX).is_err());
+}
+
+#[test]
+fn serde_round_trips() {
+    let bytes = to_vec(&sample()).expect("serializable");
+    let decoded: Record<'_> = from_bytes(&bytes).expect("valid output");
+    assert_eq!(decoded, sample());
+
+    let options = SerializerOptions {
+        structs: StructStyle::Object(ClassName::TypeName),
+        unit_variants: UnitVariantStyle::Enum(ClassName::TypeName),
+        ..SerializerOptions::default()
+    };
+    let objects = to_string_with(&sample(), options);
+    assert!(objects.starts_with("O:6:\"Record\":5:{"));
+    assert!(objects.contains("E:9:\"Kind:Leaf\";"));
+    let decoded: Record<'_> = from_str(&objects).expect("valid output");
+    assert_eq!(decoded, sample());
+
+    let value = Value::parse(objects.as_str()).expect("valid output");
+    assert_eq!(value.to_bytes(), objects.as_bytes());
+
+    let nested: Vec<Option<Vec<(i64, String)>>> = vec![None, Some(vec![(1, "a".to_string())])];
+    let encoded = to_string(&nested).expect("serializable");
+    assert_eq!(
+        encoded,
+        "a:2:{i:0;N;i:1;a:1:{i:0;a:2:{i:0;i:1;i:1;s:1:\"a\";}}}"
+    );
+    let decoded: Vec<Option<Vec<(i64, String)>>> = from_str(&encoded).expect("valid output");
+    assert_eq!(decoded, nested);
+
+    let mut writer = vec![];
+    to_writer(&mut writer, &nested).expect("serializable");
+    assert_eq!(writer, encoded.as_bytes());
+}
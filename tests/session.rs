@@ -0,0 +1,159 @@
+use phpser::*;
+
+const SESSION: &str = concat!(
+    "user_id|i:42;",
+    "cart|a:2:{i:0;O:4:\"Item\":1:{s:5:\"price\";d:9.5;}i:1;r:3;}",
+    "flash|s:9:\"Saved|ok!\";",
+    "|N;",
+    "last|R:2;",
+);
+
+#[test]
+fn round_trip() {
+    let session = Session::parse(SESSION).expect("valid session");
+    let names: Vec<&str> = session.entries().iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["user_id", "cart", "flash", "", "last"]);
+    assert!(matches!(session.entries()[0].1, Value::Int(42)));
+    assert_eq!(
+        session.to_bytes().expect("names are valid"),
+        SESSION.as_bytes()
+    );
+}
+
+#[test]
+fn trailing_data_without_delimiter_is_ignored() {
+    let session = Session::parse(&b"a|i:1;garbage"[..]).expect("valid session");
+    assert_eq!(session.entries().len(), 1);
+    assert!(Session::parse("")
+        .expect("empty session")
+        .entries()
+        .is_empty());
+}
+
+#[test]
+fn error_offset_is_absolute() {
+    assert!(matches!(
+        Session::parse("a|i:1;b|i:x;"),
+        Err(IoError::Phpser(Error::BadNumber(12)))
+    ));
+}
+
+#[test]
+fn reject_delimiter_in_name() {
+    let session = Session::new(vec![("a|b", Value::Null)]);
+    assert!(session.to_bytes().is_err());
+    let mut buf = vec![];
+    assert!(session.write_to(&mut buf).is_err());
+    assert!(buf.is_empty());
+}
+
+const BINARY: &[u8] = b"\x07user_idi:42;\x04carta:1:{i:0;s:3:\"pen\";}\x00N;";
+
+const SERIALIZE: &str = "a:3:{s:7:\"user_id\";i:42;s:4:\"cart\";a:1:{i:0;s:3:\"pen\";}s:0:\"\";N;}";
+
+#[test]
+fn binary_round_trip() {
+    let session = Session::parse_as(BINARY, SessionHandler::PhpBinary).expect("valid session");
+    let names: Vec<&[u8]> = session.entries().iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, [&b"user_id"[..], b"cart", b""]);
+    assert_eq!(
+        session
+            .to_bytes_as(SessionHandler::PhpBinary)
+            .expect("names are short"),
+        BINARY
+    );
+}
+
+#[test]
+fn binary_ignores_undef_bit() {
+    let session =
+        Session::parse_as(&b"\x81ai:1;"[..], SessionHandler::PhpBinary).expect("valid session");
+    assert_eq!(session.entries()[0].0, b"a");
+}
+
+#[test]
+fn binary_rejects_long_names() {
+    let name = "n".repeat(128);
+    let session = Session::new(vec![(name.as_str(), Value::Null)]);
+    assert!(session.to_bytes_as(SessionHandler::PhpBinary).is_err());
+    assert!(session.to_bytes_as(SessionHandler::Php).is_ok());
+}
+
+#[test]
+fn serialize_round_trip() {
+    let session =
+        Session::parse_as(SERIALIZE, SessionHandler::PhpSerialize).expect("valid session");
+    let names: Vec<&str> = session.entries().iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["user_id", "cart", ""]);
+    assert_eq!(
+        session
+            .to_bytes_as(SessionHandler::PhpSerialize)
+            .expect("any name is valid"),
+        SERIALIZE.as_bytes()
+    );
+}
+
+#[test]
+fn serialize_requires_string_keys() {
+    assert!(matches!(
+        Session::parse_as("a:1:{i:0;N;}", SessionHandler::PhpSerialize),
+        Err(IoError::Phpser(Error::BadArrayKeyType(6)))
+    ));
+    assert!(matches!(
+        Session::parse_as("N;", SessionHandler::PhpSerialize),
+        Err(IoError::Phpser(Error::BadToken(1)))
+    ));
+    assert!(Session::parse_as("", SessionHandler::PhpSerialize)
+        .expect("empty session")
+        .entries()
+        .is_empty());
+}
+
+#[test]
+fn handlers_convert() {
+    let session = Session::parse(SESSION).expect("valid session");
+    for &handler in &[
+        SessionHandler::Php,
+        SessionHandler::PhpBinary,
+        SessionHandler::PhpSerialize,
+    ] {
+        let bytes = session.to_bytes_as(handler).expect("names are valid");
+        let parsed = Session::parse_as(&bytes[..], handler).expect("valid session");
+        assert_eq!(parsed.entries().len(), session.entries().len());
+    }
+}
+
+#[test]
+fn detect() {
+    assert_eq!(
+        SessionHandler::detect(SESSION.as_bytes()),
+        Some(SessionHandler::Php)
+    );
+    assert_eq!(
+        SessionHandler::detect(BINARY),
+        Some(SessionHandler::PhpBinary)
+    );
+    assert_eq!(
+        SessionHandler::detect(SERIALIZE.as_bytes()),
+        Some(SessionHandler::PhpSerialize)
+    );
+    assert_eq!(SessionHandler::detect(b""), None);
+    assert_eq!(SessionHandler::detect(b"not a session"), None);
+}
+
+#[test]
+fn many_variables() {
+    let data: String = (0..20_000).map(|i| format!("v{}|i:{};", i, i)).collect();
+    let session = Session::parse(data.clone()).expect("valid session");
+    assert_eq!(session.entries().len(), 20_000);
+    assert_eq!(
+        session.to_bytes().expect("names are valid"),
+        data.as_bytes()
+    );
+
+    for &handler in &[SessionHandler::PhpBinary, SessionHandler::PhpSerialize] {
+        let bytes = session.to_bytes_as(handler).expect("names are valid");
+        let parsed = Session::parse_as(bytes, handler).expect("valid session");
+        assert_eq!(parsed.entries().len(), 20_000);
+    }
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "tokio")]
+
+use phpser::*;
+use tokio::io::{duplex, AsyncWriteExt};
+
+#[tokio::test]
+async fn read_values_from_duplex() {
+    let (mut client, server) = duplex(4);
+    let writer = tokio::spawn(async move {
+        for chunk in &[
+            &b"a:2:{i:0;s:5:\"h"[..],
+            b"ello\";i:1;",
+            b"d:1.5;}",
+            b"i:42;",
+        ] {
+            client.write_all(chunk).await.expect("write to duplex");
+        }
+    });
+
+    let mut reader = AsyncStringReader::new(server, 1024);
+    let value = Value::from_async_source(&mut reader)
+        .await
+        .expect("first value");
+    assert_eq!(value.to_bytes(), b"a:2:{i:0;s:5:\"hello\";i:1;d:1.5;}");
+    let value = Value::from_async_source(&mut reader)
+        .await
+        .expect("second value");
+    assert!(matches!(value, Value::Int(42)));
+    assert_eq!(reader.offset(), 37);
+
+    writer.await.expect("writer task");
+    assert!(matches!(
+        Value::from_async_source(&mut reader).await,
+        Err(IoError::Phpser(Error::UnexpectedEof))
+    ));
+}
+
+#[tokio::test]
+async fn limit_stops_reading() {
+    let (mut client, server) = duplex(64);
+    client
+        .write_all(b"s:10:\"0123456789\";")
+        .await
+        .expect("write to duplex");
+
+    let mut reader = AsyncByteReader::new(server, 8);
+    assert!(matches!(
+        Value::from_async_source(&mut reader).await,
+        Err(IoError::Phpser(Error::UnexpectedEof))
+    ));
+}
+
+#[tokio::test]
+async fn huge_declared_length_exceeds_limit() {
+    let (mut client, server) = duplex(64);
+    let writer = tokio::spawn(async move {
+        client
+            .write_all(b"a:9999999999:{i:0;N;i:1;N;")
+            .await
+            .expect("write to duplex");
+        // keep the stream open, so only the limit can end the value
+        client
+    });
+
+    let mut reader = AsyncByteReader::new(server, 16);
+    assert!(matches!(
+        Value::from_async_source(&mut reader).await,
+        Err(IoError::Phpser(Error::UnexpectedEof))
+    ));
+    drop(writer.await.expect("writer task"));
+}
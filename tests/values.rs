@@ -0,0 +1,139 @@
+use std::io::{self, Read};
+
+use phpser::*;
+
+const RECORDS: &str = "i:1;s:3:\"a;b\";a:1:{i:0;N;}O:8:\"stdClass\":0:{}";
+
+/// A reader that returns one byte per `read` call.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match (self.0.split_first(), buf.first_mut()) {
+            (Some((&byte, rest)), Some(out)) => {
+                *out = byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+fn collect<'de, S: Str<'de>>(
+    values: impl Iterator<Item = IoResult<Value<S>>>,
+) -> Vec<Result<Vec<u8>, String>> {
+    values
+        .map(|result| match result {
+            Ok(value) => Ok(value.to_bytes()),
+            Err(err) => Err(format!("{:?}", err)),
+        })
+        .collect()
+}
+
+fn expected_records() -> Vec<Result<Vec<u8>, String>> {
+    vec![
+        Ok(b"i:1;".to_vec()),
+        Ok(b"s:3:\"a;b\";".to_vec()),
+        Ok(b"a:1:{i:0;N;}".to_vec()),
+        Ok(b"O:8:\"stdClass\":0:{}".to_vec()),
+    ]
+}
+
+#[test]
+fn clean_eof() {
+    let expected = expected_records();
+    assert_eq!(collect(Values::new(Cursor::new(RECORDS))), expected);
+    assert_eq!(
+        collect(Values::new(Cursor::new(RECORDS.as_bytes()))),
+        expected
+    );
+    assert_eq!(
+        collect(Values::new(ByteReader::new(RECORDS.as_bytes(), 1 << 16))),
+        expected
+    );
+    assert_eq!(
+        collect(Values::new(StringReader::new(
+            Trickle(RECORDS.as_bytes()),
+            1 << 16
+        ))),
+        expected
+    );
+
+    assert!(collect(Values::new(Cursor::new(""))).is_empty());
+    assert!(collect(Values::new(ByteReader::new(io::empty(), 1 << 16))).is_empty());
+}
+
+#[test]
+fn mid_value_eof() {
+    let eof = format!("{:?}", IoError::Phpser(Error::UnexpectedEof));
+    for cut in 1..RECORDS.len() {
+        let input = RECORDS.get(..cut).expect("ASCII input");
+        let mut expected: Vec<_> = expected_records()
+            .into_iter()
+            .scan(0, |end, record| {
+                *end += record.as_ref().map_or(0, Vec::len);
+                Some((*end, record))
+            })
+            .take_while(|&(end, _)| end <= cut)
+            .map(|(_, record)| record)
+            .collect();
+        let complete: usize = expected
+            .iter()
+            .map(|r| r.as_ref().map_or(0, Vec::len))
+            .sum();
+        if complete < cut {
+            expected.push(Err(eof.clone()));
+        }
+
+        assert_eq!(
+            collect(Values::new(Cursor::new(input))),
+            expected,
+            "{:?}",
+            input
+        );
+        assert_eq!(
+            collect(Values::new(ByteReader::new(
+                Trickle(input.as_bytes()),
+                1 << 16
+            ))),
+            expected,
+            "{:?}",
+            input
+        );
+    }
+}
+
+#[test]
+fn errors_end_the_iteration() {
+    let mut values = Values::new(Cursor::new("i:1;x:0;i:2;"));
+    assert!(matches!(values.next(), Some(Ok(Value::Int(1)))));
+    assert!(matches!(
+        values.next(),
+        Some(Err(IoError::Phpser(Error::BadToken(5))))
+    ));
+    assert!(values.next().is_none());
+    assert!(values.next().is_none());
+}
+
+#[test]
+fn limits_apply_to_each_value() {
+    let options = ParseOptions {
+        max_elements: 2,
+        ..ParseOptions::default()
+    };
+    let input = "a:1:{i:0;N;}a:1:{i:0;N;}a:3:{i:0;N;i:1;N;i:2;N;}";
+    let results = collect(Values::with_options(Cursor::new(input), &options));
+    match results.as_slice() {
+        [Ok(_), Ok(_), Err(err)] => assert!(err.contains("ElementLimitExceeded"), "{}", err),
+        results => panic!("{:?}", results),
+    }
+}
+
+#[test]
+fn into_source() {
+    let mut values = Values::new(Cursor::new("i:1;i:2;"));
+    assert!(matches!(values.next(), Some(Ok(Value::Int(1)))));
+    let source = values.into_source();
+    assert_eq!(source.offset(), 4);
+}